@@ -0,0 +1,49 @@
+use clap::Parser;
+use discord_rust_scraper::mock_server::{MockDiscordServer, MockServerConfig};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Serves fixture JSON files like the Discord API would, see `mock_server` for the layout.
+#[derive(Parser)]
+struct Args {
+    fixtures_dir: PathBuf,
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    address: SocketAddr,
+    #[clap(long = "max_page_size", default_value_t = 100)]
+    max_page_size: usize,
+    // Requests allowed per route and window, unlimited by default.
+    #[clap(long = "rate_limit")]
+    rate_limit: Option<u32>,
+    #[clap(long = "rate_limit_reset_after_ms", default_value_t = 1000)]
+    rate_limit_reset_after_ms: u64,
+    #[clap(long = "too_many_requests_every")]
+    too_many_requests_every: Option<u64>,
+    #[clap(long = "service_unavailable_every")]
+    service_unavailable_every: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt::init();
+
+    let args = Args::parse();
+    let mut server = MockDiscordServer::start(MockServerConfig {
+        fixtures_dir: args.fixtures_dir,
+        address: args.address,
+        max_page_size: args.max_page_size,
+        rate_limit: args.rate_limit,
+        rate_limit_reset_after: Duration::from_millis(args.rate_limit_reset_after_ms),
+        too_many_requests_every: args.too_many_requests_every,
+        service_unavailable_every: args.service_unavailable_every,
+    })
+    .await?;
+    tracing::info!(
+        "Mock Discord API listening, scrape it with `--api_base_url {}`",
+        server.base_url()
+    );
+    server.wait().await;
+
+    Ok(())
+}
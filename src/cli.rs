@@ -1,8 +1,27 @@
-use crate::scraper::{convert_jsonl_file_into_json, Scraper};
+use crate::discord_api::cassette::ReplayTransport;
+use crate::discord_api::{
+    retry, DiscordApi, RetryPolicy, DEFAULT_DISCORD_API_BASE_URL, DEFAULT_DISCORD_API_VERSION,
+};
+use crate::scraper::{
+    convert_jsonl_file_into_csv, convert_jsonl_file_into_json, convert_jsonl_file_into_parquet,
+    read_jsonl_transcript, write_transcript, ChannelScrapeSummary, ParseMode, ScrapeBounds,
+    Scraper,
+};
+use crate::transcript::html::DEFAULT_MESSAGES_PER_PAGE;
+use crate::transcript::{Transcript, TranscriptFormat};
+use crate::utils::channel_filter::{ChannelFilter, ScrapableChannelType};
+use crate::utils::compression::{self, Compression};
+use crate::utils::csv_saver::{CsvColumn, CsvOptions};
 use crate::utils::message_saver::SaveTarget;
-use clap::Parser;
+use crate::utils::parquet_saver::DEFAULT_ROW_GROUP_SIZE;
+use chrono::{DateTime, Utc};
+use clap::{ArgGroup, Parser};
 use color_eyre::eyre;
+use futures::StreamExt;
+use regex::Regex;
+use std::collections::HashSet;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 struct Cli {
@@ -12,23 +31,332 @@ struct Cli {
 
 #[derive(Parser)]
 enum Command {
+    ConvertToCsv(ConvertToCsv),
     ConvertToJson(ConvertToJson),
-    Scrape(Scrape),
+    ConvertToParquet(ConvertToParquet),
+    Render(Render),
+    Scrape(Box<Scrape>),
 }
 
 #[derive(Parser)]
 struct ConvertToJson {
     input_file: PathBuf,
+    #[clap(long)]
+    strict: bool,
+}
+
+#[derive(Parser)]
+struct ConvertToCsv {
+    input_file: PathBuf,
+    #[clap(long)]
+    strict: bool,
+    // Separates the values with tabs instead of commas.
+    #[clap(long)]
+    tsv: bool,
+    #[clap(long, value_enum, num_args = 1.., default_values_t = CsvColumn::DEFAULT)]
+    columns: Vec<CsvColumn>,
+    #[clap(long = "no_header")]
+    no_header: bool,
+    // Leaves cells that look like spreadsheet formulas as they are.
+    #[clap(long = "no_formula_escape")]
+    no_formula_escape: bool,
+}
+
+#[derive(Parser)]
+struct ConvertToParquet {
+    input_file: PathBuf,
+    #[clap(long)]
+    strict: bool,
+    // Number of messages per row group.
+    #[clap(long = "row_group_size", default_value_t = DEFAULT_ROW_GROUP_SIZE)]
+    row_group_size: usize,
+}
+
+#[derive(Parser)]
+struct Render {
+    // A JSONL archive, or nothing when reading the channel from a database.
+    #[clap(required_unless_present_any = ["sql", "sqlite"], conflicts_with_all = ["sql", "sqlite"])]
+    input_file: Option<PathBuf>,
+    #[clap(long, conflicts_with = "sqlite", requires = "channel_id")]
+    sql: Option<String>,
+    #[clap(long, requires = "channel_id")]
+    sqlite: Option<PathBuf>,
+    // The channel to read from the database, along with its threads.
+    #[clap(long = "channel_id")]
+    channel_id: Option<u64>,
+    #[clap(long, value_enum, default_value_t = TranscriptFormat::Html)]
+    format: TranscriptFormat,
+    // Defaults to the input file with the extension of the format, or `storage/<channel_id>.<ext>`.
+    #[clap(long)]
+    output: Option<PathBuf>,
+    #[clap(long)]
+    title: Option<String>,
+    // Only HTML transcripts are split into pages.
+    #[clap(long = "messages_per_page", default_value_t = DEFAULT_MESSAGES_PER_PAGE)]
+    messages_per_page: usize,
+    #[clap(long)]
+    strict: bool,
+}
+
+impl Render {
+    // The transcript along with the default path of its first page.
+    async fn read_transcript(&self) -> eyre::Result<(Transcript, PathBuf)> {
+        if let Some(input_file) = &self.input_file {
+            let transcript =
+                read_jsonl_transcript(input_file, parse_mode_from_flag(self.strict)).await?;
+            let output_path =
+                compression::uncompressed_path(input_file).with_extension(self.format.extension());
+            return Ok((transcript, output_path));
+        }
+        let channel_id = self
+            .channel_id
+            .ok_or_else(|| eyre::eyre!("`--channel_id` is required to read from a database"))?;
+        let save_target = match (&self.sql, &self.sqlite) {
+            (Some(database_url), _) => SaveTarget::Sql(database_url.clone()),
+            (None, Some(database_path)) => SaveTarget::Sqlite(database_path.clone()),
+            (None, None) => return Err(eyre::eyre!("Nothing to render")),
+        };
+        let messages = save_target
+            .connect()
+            .await?
+            .channel_messages(channel_id)
+            .await?;
+        Ok((
+            Transcript::new(format!("Channel {}", channel_id), messages),
+            PathBuf::from(format!(
+                "storage/{}.{}",
+                channel_id,
+                self.format.extension()
+            )),
+        ))
+    }
 }
 
 #[derive(Parser)]
+// `--columns`, `--no_header` and `--no_formula_escape` only apply to `--csv` and `--tsv`.
+#[clap(group(ArgGroup::new("csv_output").args(["csv", "tsv"])))]
 struct Scrape {
     #[clap(long = "bot_token")]
     bot_token: String,
-    #[clap(long = "channel_ids", value_parser, num_args = 1..)]
+    #[clap(long = "channel_ids", value_parser, num_args = 1.., required_unless_present = "guild_id")]
     channel_ids: Vec<u64>,
-    #[clap(long)]
+    #[clap(long = "guild_id")]
+    guild_id: Option<u64>,
+    // Regular expressions matched against the channel names of the guild.
+    #[clap(long = "include_channels", num_args = 1.., requires = "guild_id")]
+    include_channels: Vec<Regex>,
+    #[clap(long = "exclude_channels", num_args = 1.., requires = "guild_id")]
+    exclude_channels: Vec<Regex>,
+    // Category ids or names.
+    #[clap(long = "include_categories", num_args = 1.., requires = "guild_id")]
+    include_categories: Vec<String>,
+    #[clap(long = "exclude_categories", num_args = 1.., requires = "guild_id")]
+    exclude_categories: Vec<String>,
+    #[clap(
+        long = "channel_types",
+        value_enum,
+        num_args = 1..,
+        requires = "guild_id",
+        default_values_t = ScrapableChannelType::ALL
+    )]
+    channel_types: Vec<ScrapableChannelType>,
+    // MySQL connection string, or a PostgreSQL one (`postgres://`, `postgresql://`).
+    #[clap(long, conflicts_with = "sqlite")]
     sql: Option<String>,
+    // Path of a SQLite database, created along with its tables if needed.
+    #[clap(long)]
+    sqlite: Option<PathBuf>,
+    // One Parquet file per channel, written anew by every scrape.
+    #[clap(long, conflicts_with_all = ["sql", "sqlite", "resume", "incremental"])]
+    parquet: bool,
+    // Number of messages per row group of the Parquet files.
+    #[clap(
+        long = "row_group_size",
+        default_value_t = DEFAULT_ROW_GROUP_SIZE,
+        requires = "parquet"
+    )]
+    row_group_size: usize,
+    // One CSV file per channel, written anew by every scrape.
+    #[clap(long, conflicts_with_all = ["sql", "sqlite", "parquet", "resume", "incremental"])]
+    csv: bool,
+    // Same as `--csv` with the values separated by tabs.
+    #[clap(long, conflicts_with_all = ["csv", "sql", "sqlite", "parquet", "resume", "incremental"])]
+    tsv: bool,
+    #[clap(
+        long,
+        value_enum,
+        num_args = 1..,
+        default_values_t = CsvColumn::DEFAULT,
+        requires = "csv_output"
+    )]
+    columns: Vec<CsvColumn>,
+    #[clap(long = "no_header", requires = "csv_output")]
+    no_header: bool,
+    #[clap(long = "no_formula_escape", requires = "csv_output")]
+    no_formula_escape: bool,
+    // Writes `storage/<channel_name>-<channel_id>.jsonl.gz` or `.jsonl.zst` instead of plain JSONL.
+    #[clap(long, value_enum, conflicts_with_all = ["sql", "sqlite", "parquet", "csv", "tsv"])]
+    compress: Option<Compression>,
+    #[clap(long = "api_base_url", default_value = DEFAULT_DISCORD_API_BASE_URL)]
+    api_base_url: String,
+    #[clap(long = "api_version", default_value_t = DEFAULT_DISCORD_API_VERSION)]
+    api_version: u8,
+    #[clap(long = "timeout_secs")]
+    timeout_secs: Option<u64>,
+    #[clap(long = "connect_timeout_secs")]
+    connect_timeout_secs: Option<u64>,
+    #[clap(long = "user_agent")]
+    user_agent: Option<String>,
+    #[clap(long)]
+    raw: bool,
+    #[clap(long)]
+    strict: bool,
+    #[clap(long)]
+    resume: bool,
+    #[clap(long, conflicts_with = "resume")]
+    incremental: bool,
+    #[clap(long = "include_threads")]
+    include_threads: bool,
+    // Number of channels scraped at the same time.
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    concurrency: u16,
+    // RFC 3339 dates, e.g. 2024-01-01T00:00:00Z
+    #[clap(long)]
+    since: Option<DateTime<Utc>>,
+    #[clap(long)]
+    until: Option<DateTime<Utc>>,
+    #[clap(long = "after_id")]
+    after_id: Option<u64>,
+    #[clap(long = "before_id")]
+    before_id: Option<u64>,
+    #[clap(long = "max_attempts", default_value_t = retry::DEFAULT_MAX_ATTEMPTS)]
+    max_attempts: u32,
+    #[clap(long = "initial_backoff_ms", default_value_t = retry::DEFAULT_INITIAL_BACKOFF_MS)]
+    initial_backoff_ms: u64,
+    #[clap(long = "max_backoff_ms", default_value_t = retry::DEFAULT_MAX_BACKOFF_MS)]
+    max_backoff_ms: u64,
+    #[clap(
+        long = "max_rate_limited_attempts",
+        default_value_t = retry::DEFAULT_MAX_RATE_LIMITED_ATTEMPTS
+    )]
+    max_rate_limited_attempts: u32,
+    // Writes every request/response pair, the Authorization header scrubbed, into a cassette.
+    #[clap(long = "record_cassette", conflicts_with = "replay_cassette")]
+    record_cassette: Option<PathBuf>,
+    // Serves the responses of a recorded cassette instead of calling the API.
+    #[clap(long = "replay_cassette")]
+    replay_cassette: Option<PathBuf>,
+    #[clap(
+        long = "retry_status_codes",
+        num_args = 1..,
+        default_values_t = retry::DEFAULT_RETRYABLE_STATUS_CODES
+    )]
+    retry_status_codes: Vec<u16>,
+}
+
+impl Scrape {
+    fn channel_filter(&self) -> ChannelFilter {
+        ChannelFilter {
+            include_names: self.include_channels.clone(),
+            exclude_names: self.exclude_channels.clone(),
+            include_categories: self.include_categories.clone(),
+            exclude_categories: self.exclude_categories.clone(),
+            channel_types: self.channel_types.clone(),
+        }
+    }
+
+    async fn build_discord_api(&self) -> eyre::Result<DiscordApi> {
+        let mut builder = DiscordApi::builder(&self.bot_token, false)
+            .base_url(&self.api_base_url)
+            .api_version(self.api_version)
+            .keep_raw_messages(self.raw)
+            .retry_policy(RetryPolicy {
+                max_attempts: self.max_attempts,
+                initial_backoff: Duration::from_millis(self.initial_backoff_ms),
+                max_backoff: Duration::from_millis(self.max_backoff_ms),
+                retryable_status_codes: self.retry_status_codes.clone(),
+                max_rate_limited_attempts: self.max_rate_limited_attempts,
+            });
+        if let Some(timeout_secs) = self.timeout_secs {
+            builder = builder.timeout(Duration::from_secs(timeout_secs));
+        }
+        if let Some(connect_timeout_secs) = self.connect_timeout_secs {
+            builder = builder.connect_timeout(Duration::from_secs(connect_timeout_secs));
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(record_cassette) = &self.record_cassette {
+            builder = builder.record_to(record_cassette);
+        }
+        if let Some(replay_cassette) = &self.replay_cassette {
+            builder = builder.transport(ReplayTransport::from_file(replay_cassette).await?);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+fn log_channel_scrape_summary(summary: &ChannelScrapeSummary) {
+    if summary.scraped_threads > 0 {
+        tracing::info!(
+            "Scraped {} thread(s) of channel `{}`.",
+            summary.scraped_threads,
+            summary.channel_name
+        );
+    }
+    if let Some(path) = &summary.output_path {
+        tracing::info!(
+            "Successfully scraped channel `{}` ({}), took {}s, saved {} messages. Output at `{}`",
+            summary.channel_name,
+            summary.channel_id,
+            summary.duration.as_secs(),
+            summary.saved_messages,
+            path.display()
+        );
+    } else {
+        tracing::info!(
+            "Successfully scraped channel `{}` ({}), took {}s, saved {} messages. Saved to database",
+            summary.channel_name,
+            summary.channel_id,
+            summary.duration.as_secs(),
+            summary.saved_messages
+        );
+    }
+    if summary.rejected_messages > 0 {
+        tracing::warn!(
+            "Rejected {} message(s) of channel `{}`, reasons: {:#?}",
+            summary.rejected_messages,
+            summary.channel_name,
+            summary.rejection_reasons
+        );
+    }
+}
+
+fn csv_options_from_flags(
+    tsv: bool,
+    columns: &[CsvColumn],
+    no_header: bool,
+    no_formula_escape: bool,
+) -> CsvOptions {
+    let csv_options = if tsv {
+        CsvOptions::tsv()
+    } else {
+        CsvOptions::default()
+    };
+    CsvOptions {
+        columns: columns.to_vec(),
+        header: !no_header,
+        escape_formulas: !no_formula_escape,
+        ..csv_options
+    }
+}
+
+fn parse_mode_from_flag(strict: bool) -> ParseMode {
+    if strict {
+        ParseMode::Strict
+    } else {
+        ParseMode::Lenient
+    }
 }
 
 pub async fn run() -> eyre::Result<()> {
@@ -36,36 +364,149 @@ pub async fn run() -> eyre::Result<()> {
 
     match cli.command {
         Command::Scrape(args) => {
-            let scraper = Scraper::new(args.bot_token, false);
-            let save_target = if let Some(database_url) = args.sql {
-                SaveTarget::Sql(database_url)
+            let scraper = Scraper::new(args.build_discord_api().await?)
+                .with_parse_mode(parse_mode_from_flag(args.strict))
+                .with_resume(args.resume)
+                .with_incremental(args.incremental)
+                .with_include_threads(args.include_threads)
+                .with_bounds(ScrapeBounds::new(
+                    args.since,
+                    args.until,
+                    args.after_id,
+                    args.before_id,
+                ));
+            let save_target = if let Some(database_url) = &args.sql {
+                SaveTarget::Sql(database_url.clone())
+            } else if let Some(database_path) = &args.sqlite {
+                SaveTarget::Sqlite(database_path.clone())
+            } else if args.parquet {
+                SaveTarget::Parquet(args.row_group_size)
+            } else if args.csv || args.tsv {
+                SaveTarget::Csv(csv_options_from_flags(
+                    args.tsv,
+                    &args.columns,
+                    args.no_header,
+                    args.no_formula_escape,
+                ))
             } else {
-                SaveTarget::Jsonl
+                SaveTarget::Jsonl(args.compress)
             };
 
-            for channel_id in args.channel_ids {
-                let (output_path, duration) =
-                    scraper.scrape_channel(channel_id, &save_target).await?;
-                if let Some(path) = output_path {
-                    tracing::info!(
-                        "Successfully scraped channel `{}`, took {}s. Output at `{}`",
-                        channel_id,
-                        duration,
-                        path.display()
-                    );
-                } else {
-                    tracing::info!(
-                        "Successfully scraped channel `{}`, took {}s. Saved to database",
-                        channel_id,
-                        duration
-                    );
+            // Channels found in the guild are skipped when unreadable, explicit ones fail. A
+            // channel listed twice, e.g. both found in the guild and in `--channel_ids`, is only
+            // scraped once so that no two scrapes write into its output at the same time.
+            let mut guild_channel_ids = HashSet::new();
+            let mut listed_channel_ids = HashSet::new();
+            let mut channel_ids = Vec::new();
+            if let Some(guild_id) = args.guild_id {
+                let guild_channels = scraper
+                    .list_guild_channels(guild_id, &args.channel_filter())
+                    .await?;
+                tracing::info!(
+                    "Found {} channel(s) to scrape in guild `{}`.",
+                    guild_channels.len(),
+                    guild_id
+                );
+                for channel in guild_channels {
+                    if channel.is_forum() && !args.include_threads {
+                        tracing::info!(
+                            "Skipping forum channel `{}`, its posts are threads, see `--include_threads`.",
+                            channel.name.unwrap_or_default()
+                        );
+                        continue;
+                    }
+                    guild_channel_ids.insert(channel.id);
+                    if listed_channel_ids.insert(channel.id) {
+                        channel_ids.push(channel.id);
+                    }
                 }
             }
+            for &channel_id in &args.channel_ids {
+                if listed_channel_ids.insert(channel_id) {
+                    channel_ids.push(channel_id);
+                }
+            }
+
+            let mut failed_channels = 0;
+            let mut results = std::pin::pin!(
+                scraper
+                    .scrape_channels(channel_ids, &save_target, args.concurrency.into())
+                    .await?
+            );
+            while let Some((channel_id, result)) = results.next().await {
+                match result {
+                    Ok(summary) => log_channel_scrape_summary(&summary),
+                    Err(error)
+                        if error.is_missing_access() && guild_channel_ids.contains(&channel_id) =>
+                    {
+                        tracing::warn!("Skipping channel `{}`, the bot can't read it.", channel_id)
+                    }
+                    Err(error) => {
+                        tracing::error!("Failed to scrape channel `{}`: {}", channel_id, error);
+                        failed_channels += 1;
+                    }
+                }
+            }
+            if failed_channels > 0 {
+                return Err(eyre::eyre!("Failed to scrape {} channel(s).", failed_channels));
+            }
+        }
+        Command::Render(args) => {
+            let (transcript, default_output_path) = args.read_transcript().await?;
+            let transcript = match &args.title {
+                Some(title) => transcript.with_title(title),
+                None => transcript,
+            };
+            let output_path = args.output.clone().unwrap_or(default_output_path);
+            if let Some(output_dir) = output_path.parent() {
+                tokio::fs::create_dir_all(output_dir).await?;
+            }
+            let file_paths = write_transcript(
+                &transcript,
+                &output_path,
+                args.format,
+                args.messages_per_page,
+            )
+            .await?;
+            tracing::info!(
+                "Rendered {} messages into {} file(s) at `{}`",
+                transcript.messages().len(),
+                file_paths.len(),
+                output_path.display()
+            );
+        }
+        Command::ConvertToCsv(args) => {
+            let csv_file_path = convert_jsonl_file_into_csv(
+                &args.input_file,
+                parse_mode_from_flag(args.strict),
+                &csv_options_from_flags(
+                    args.tsv,
+                    &args.columns,
+                    args.no_header,
+                    args.no_formula_escape,
+                ),
+            )
+            .await?;
+            tracing::info!("Converted JSONL to CSV at `{}`", csv_file_path.display());
         }
         Command::ConvertToJson(args) => {
-            let json_file_path = convert_jsonl_file_into_json(&args.input_file).await?;
+            let json_file_path =
+                convert_jsonl_file_into_json(&args.input_file, parse_mode_from_flag(args.strict))
+                    .await?;
             tracing::info!("Converted JSONL to JSON at `{}`", json_file_path.display());
         }
+        Command::ConvertToParquet(args) => {
+            let parquet_file_path = convert_jsonl_file_into_parquet(
+                &args.input_file,
+                parse_mode_from_flag(args.strict),
+                args.row_group_size,
+            )
+            .await?;
+            tracing::info!(
+                "Converted JSONL to Parquet at `{}`",
+                parquet_file_path.display()
+            );
+        }
     }
 
     Ok(())
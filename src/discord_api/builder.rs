@@ -0,0 +1,123 @@
+use super::cassette::RecordingTransport;
+use super::transport::{ReqwestTransport, Transport};
+use super::{
+    DiscordApi, DiscordAuth, RateLimiter, RetryPolicy, DEFAULT_DISCORD_API_BASE_URL,
+    DEFAULT_DISCORD_API_VERSION,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub struct DiscordApiBuilder {
+    auth: DiscordAuth,
+    base_url: String,
+    api_version: u8,
+    transport: Option<Box<dyn Transport>>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent: Option<String>,
+    keep_raw_messages: bool,
+    retry_policy: RetryPolicy,
+    cassette_path: Option<PathBuf>,
+}
+
+impl DiscordApiBuilder {
+    pub fn new<S: ToString>(token: S, personal: bool) -> Self {
+        Self {
+            auth: DiscordAuth::new(token, personal),
+            base_url: DEFAULT_DISCORD_API_BASE_URL.to_string(),
+            api_version: DEFAULT_DISCORD_API_VERSION,
+            transport: None,
+            timeout: None,
+            connect_timeout: None,
+            user_agent: None,
+            keep_raw_messages: false,
+            retry_policy: RetryPolicy::default(),
+            cassette_path: None,
+        }
+    }
+
+    // Base URL without the version segment, e.g. `http://127.0.0.1:8080/api`.
+    pub fn base_url<S: ToString>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.to_string().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn api_version(mut self, api_version: u8) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    // Uses the given client as is, `connect_timeout` is ignored in that case since it can only be
+    // set while building a client.
+    pub fn reqwest_client(self, reqwest_client: reqwest::Client) -> Self {
+        self.transport(ReqwestTransport::new(reqwest_client))
+    }
+
+    // Sends the requests through the given transport instead of reqwest, e.g. a
+    // `FakeTransport` in tests. `connect_timeout` is ignored in that case.
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    // Total time allowed for a single request, applied on every request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn user_agent<S: ToString>(mut self, user_agent: S) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    // Keeps the untouched API object on every `Message` so archives stay lossless.
+    pub fn keep_raw_messages(mut self, keep_raw_messages: bool) -> Self {
+        self.keep_raw_messages = keep_raw_messages;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // Records every request/response pair into the given cassette, see
+    // `ReplayTransport` to serve them back.
+    pub fn record_to<P: Into<PathBuf>>(mut self, cassette_path: P) -> Self {
+        self.cassette_path = Some(cassette_path.into());
+        self
+    }
+
+    pub fn build(self) -> Result<DiscordApi, reqwest::Error> {
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                let mut client_builder = reqwest::Client::builder();
+                if let Some(connect_timeout) = self.connect_timeout {
+                    client_builder = client_builder.connect_timeout(connect_timeout);
+                }
+                Box::new(ReqwestTransport::new(client_builder.build()?))
+            }
+        };
+        let transport = match self.cassette_path {
+            Some(cassette_path) => Box::new(RecordingTransport::new(transport, cassette_path)),
+            None => transport,
+        };
+        Ok(DiscordApi {
+            transport,
+            auth: self.auth,
+            api_url: format!("{}/v{}", self.base_url, self.api_version),
+            timeout: self.timeout,
+            user_agent: self.user_agent,
+            keep_raw_messages: self.keep_raw_messages,
+            rate_limiter: RateLimiter::new(),
+            retry_policy: self.retry_policy,
+        })
+    }
+}
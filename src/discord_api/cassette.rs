@@ -0,0 +1,220 @@
+use super::transport::{HttpRequest, HttpResponse, Transport, TransportError};
+use async_trait::async_trait;
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+// A cassette is a JSONL file with one request/response pair per line, matched on the method and
+// the path and query of the url so a recording replays whatever base URL it was made against.
+
+const SCRUBBED: &str = "[scrubbed]";
+// Headers that carry credentials, their values never make it into a cassette.
+const SCRUBBED_HEADERS: [HeaderName; 3] =
+    [header::AUTHORIZATION, header::COOKIE, header::SET_COOKIE];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+// JSON bodies are kept as JSON so cassettes stay readable and editable, anything else as text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_text: Option<String>,
+}
+
+impl Interaction {
+    fn new(request: &HttpRequest, response: &HttpResponse) -> Self {
+        let body = serde_json::from_slice::<Value>(response.body()).ok();
+        let body_text = body
+            .is_none()
+            .then(|| String::from_utf8_lossy(response.body()).into_owned());
+        Self {
+            request: RecordedRequest {
+                method: request.method.to_string(),
+                url: path_and_query(&request.url),
+                headers: scrubbed_headers(&request.headers),
+            },
+            response: RecordedResponse {
+                status: response.status().as_u16(),
+                headers: scrubbed_headers(response.headers()),
+                body,
+                body_text,
+            },
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("{} {}", self.request.method, self.request.url)
+    }
+}
+
+impl RecordedResponse {
+    fn to_http_response(&self) -> Result<HttpResponse, TransportError> {
+        let status = StatusCode::from_u16(self.status)?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        let body = match (&self.body, &self.body_text) {
+            (Some(body), _) => serde_json::to_vec(body)?,
+            (None, Some(body_text)) => body_text.clone().into_bytes(),
+            (None, None) => Vec::new(),
+        };
+        Ok(HttpResponse::new(status, headers, body))
+    }
+}
+
+// `https://discord.com/api/v9/channels/1?limit=1` -> `/api/v9/channels/1?limit=1`
+fn path_and_query(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        },
+        Err(_) => url.to_string(),
+    }
+}
+
+fn scrubbed_headers(header_map: &HeaderMap) -> BTreeMap<String, String> {
+    header_map
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = if SCRUBBED_HEADERS.contains(name) {
+                SCRUBBED
+            } else {
+                value.to_str().ok()?
+            };
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+// Sends through the inner transport and appends every request/response pair to the cassette,
+// which is started over by the first request.
+pub struct RecordingTransport<T> {
+    inner: T,
+    cassette_path: PathBuf,
+    cassette: Mutex<Option<File>>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new<P: Into<PathBuf>>(inner: T, cassette_path: P) -> Self {
+        Self {
+            inner,
+            cassette_path: cassette_path.into(),
+            cassette: Mutex::new(None),
+        }
+    }
+
+    async fn record(&self, interaction: &Interaction) -> color_eyre::eyre::Result<()> {
+        let mut line = serde_json::to_string(interaction)?;
+        line.push('\n');
+        let mut cassette = self.cassette.lock().await;
+        if cassette.is_none() {
+            if let Some(parent) = self.cassette_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            *cassette = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&self.cassette_path)
+                    .await?,
+            );
+        }
+        if let Some(file) = cassette.as_mut() {
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RecordingTransport<T> {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let response = self.inner.send(request.clone()).await?;
+        if let Err(error) = self.record(&Interaction::new(&request, &response)).await {
+            tracing::error!(
+                "Failed to record into the cassette `{}`: {:#?}",
+                self.cassette_path.display(),
+                error
+            );
+        }
+        Ok(response)
+    }
+}
+
+// Serves the responses of a cassette, those recorded for the same request in recording order.
+// A request that wasn't recorded (or not that many times) fails.
+pub struct ReplayTransport {
+    interactions: std::sync::Mutex<HashMap<String, VecDeque<RecordedResponse>>>,
+}
+
+impl ReplayTransport {
+    pub fn new(interactions: Vec<Interaction>) -> Self {
+        let mut interactions_by_key: HashMap<String, VecDeque<RecordedResponse>> = HashMap::new();
+        for interaction in interactions {
+            interactions_by_key
+                .entry(interaction.key())
+                .or_default()
+                .push_back(interaction.response);
+        }
+        Self {
+            interactions: std::sync::Mutex::new(interactions_by_key),
+        }
+    }
+
+    pub async fn from_file(cassette_path: &Path) -> color_eyre::eyre::Result<Self> {
+        let cassette = fs::read_to_string(cassette_path).await?;
+        let interactions = cassette
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str::<Interaction>)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(interactions))
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let key = format!("{} {}", request.method, path_and_query(&request.url));
+        let recorded_response = self
+            .interactions
+            .lock()
+            .unwrap()
+            .get_mut(&key)
+            .and_then(VecDeque::pop_front);
+        match recorded_response {
+            Some(recorded_response) => recorded_response.to_http_response(),
+            None => Err(format!("No recorded response left for `{}`", key).into()),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::transport::{HttpRequest, HttpResponse, Transport, TransportError};
+use super::{DEFAULT_DISCORD_API_BASE_URL, DEFAULT_DISCORD_API_VERSION};
+use async_trait::async_trait;
+use reqwest::{header::HeaderMap, Method, StatusCode};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+// In-memory transport serving canned responses keyed by method and relative url, e.g.
+// `GET channels/1/messages?limit=100`. Responses queued for the same route are served in order
+// and the last one keeps being served, so a route can fail a few times and then succeed. Routes
+// without a response get a 404 like unknown Discord resources do.
+pub struct FakeTransport {
+    api_url: String,
+    routes: Mutex<HashMap<String, VecDeque<HttpResponse>>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl Default for FakeTransport {
+    fn default() -> Self {
+        Self::new(format!(
+            "{}/v{}",
+            DEFAULT_DISCORD_API_BASE_URL, DEFAULT_DISCORD_API_VERSION
+        ))
+    }
+}
+
+impl FakeTransport {
+    // `api_url` is the base URL with the version segment the `DiscordApi` is built with.
+    pub fn new<S: ToString>(api_url: S) -> Self {
+        Self {
+            api_url: api_url.to_string(),
+            routes: Mutex::new(HashMap::new()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn route_key(method: &Method, relative_url: &str) -> String {
+        format!("{} {}", method, relative_url)
+    }
+
+    pub fn respond(&self, method: Method, relative_url: &str, response: HttpResponse) {
+        self.routes
+            .lock()
+            .unwrap()
+            .entry(Self::route_key(&method, relative_url))
+            .or_default()
+            .push_back(response);
+    }
+
+    pub fn respond_json<T: Serialize>(&self, relative_url: &str, status: StatusCode, body: &T) {
+        let body = serde_json::to_vec(body).expect("canned bodies serialize into JSON");
+        self.respond(
+            Method::GET,
+            relative_url,
+            HttpResponse::new(status, HeaderMap::new(), body),
+        );
+    }
+
+    // Relative urls of the requests sent so far, in order.
+    pub fn requested_urls(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| self.relative_url(&request.url).to_string())
+            .collect()
+    }
+
+    fn relative_url<'a>(&self, url: &'a str) -> &'a str {
+        url.strip_prefix(&self.api_url)
+            .map(|url| url.trim_start_matches('/'))
+            .unwrap_or(url)
+    }
+}
+
+#[async_trait]
+impl Transport for FakeTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let route_key = Self::route_key(&request.method, self.relative_url(&request.url));
+        self.requests.lock().unwrap().push(request);
+        let mut routes = self.routes.lock().unwrap();
+        let response = match routes.get_mut(&route_key) {
+            Some(responses) if responses.len() > 1 => responses.pop_front(),
+            Some(responses) => responses.front().cloned(),
+            None => None,
+        };
+        Ok(response.unwrap_or_else(|| {
+            HttpResponse::new(
+                StatusCode::NOT_FOUND,
+                HeaderMap::new(),
+                r#"{"message": "Unknown route", "code": 0}"#,
+            )
+        }))
+    }
+}
@@ -0,0 +1,22 @@
+use super::models::Channel;
+use super::{DiscordApi, DiscordApiError, ParseError};
+use reqwest::Method;
+
+impl DiscordApi {
+    pub async fn get_channel(&self, channel_id: u64) -> Result<Channel, DiscordApiError> {
+        let url = format!("channels/{}", channel_id);
+        let response = self
+            .request_with_relative_url_and_auth_header(Method::GET, &url)
+            .await?;
+        let status = response.status().as_u16();
+        match status {
+            200 => response.json::<Channel>().map_err(|error| {
+                DiscordApiError::ParseResponse(ParseError::DeserializeBodyIntoJson(error))
+            }),
+            _ => Err(DiscordApiError::UnexpectedResponseStatusCode(
+                status,
+                Some(Box::new(response)),
+            )),
+        }
+    }
+}
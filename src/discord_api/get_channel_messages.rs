@@ -1,90 +1,242 @@
+use super::models::{
+    Attachment, DiscordMessage, Embed, MessageReference, Reaction, Sticker, User,
+};
 use super::{DiscordApi, DiscordApiError, ParseError};
-use reqwest::{Method, Response};
+use chrono::{DateTime, Utc};
+use super::transport::HttpResponse;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+// The first five fields are the original flat archive format, the rest are defaulted so archives
+// written before they existed can still be read back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub channel_id: u64,
+    // Set for messages of threads and forum posts, `channel_id` being the thread itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_channel_id: Option<u64>,
     pub author_id: u64,
     pub message_id: u64,
     pub message: String,
     pub has_media: bool,
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub edited_timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub author: Option<User>,
+    #[serde(default, rename = "type")]
+    pub kind: u8,
+    #[serde(default)]
+    pub flags: u64,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub tts: bool,
+    #[serde(default)]
+    pub mention_everyone: bool,
+    #[serde(default)]
+    pub mentions: Vec<User>,
+    #[serde(default)]
+    pub mention_roles: Vec<u64>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    #[serde(default)]
+    pub message_reference: Option<MessageReference>,
+    #[serde(default)]
+    pub sticker_items: Vec<Sticker>,
+    // The untouched API object, only kept when scraping in raw mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<serde_json::Value>,
 }
 
-impl DiscordApi {
-    async fn process_messages(
-        response: Response,
+// An object of a messages page that couldn't be parsed into a `Message`, kept for quarantine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedMessage {
+    pub channel_id: u64,
+    pub message_id: Option<u64>,
+    pub reason: String,
+    pub raw: serde_json::Value,
+}
+
+#[derive(Debug, Default)]
+pub struct MessagePage {
+    pub messages: Vec<Message>,
+    pub rejected: Vec<RejectedMessage>,
+    // Ids of the oldest and newest objects of the page whether they parsed or not, the cursors for
+    // the next page when paging backwards and forwards respectively.
+    pub oldest_message_id: Option<u64>,
+    pub newest_message_id: Option<u64>,
+}
+
+impl MessagePage {
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.rejected.is_empty()
+    }
+
+    pub fn set_parent_channel_id(&mut self, parent_channel_id: Option<u64>) {
+        for message in &mut self.messages {
+            message.parent_channel_id = parent_channel_id;
+        }
+    }
+
+    // Keeps the messages whose id matches, the paging cursors are left as they were. Rejected
+    // objects without an id are kept since there's no telling where they belong.
+    pub fn retain<F: Fn(u64) -> bool>(&mut self, keep_message_id: F) {
+        self.messages
+            .retain(|message| keep_message_id(message.message_id));
+        self.rejected.retain(|rejected_message| {
+            rejected_message.message_id.is_none_or(&keep_message_id)
+        });
+    }
+}
+
+impl Message {
+    // Also the way to re-parse archived raw objects with a newer version of the models.
+    pub fn from_raw(
         channel_id: u64,
-        wait_for_ratelimit: bool,
-    ) -> Result<Vec<Message>, DiscordApiError> {
-        if wait_for_ratelimit {
-            DiscordApi::handle_rate_limit_wait(response.headers()).await;
+        raw_message: &serde_json::Value,
+        keep_raw: bool,
+    ) -> Result<Self, serde_json::Error> {
+        let discord_message = DiscordMessage::deserialize(raw_message)?;
+        let mut message = Self::from_discord_message(channel_id, discord_message);
+        if keep_raw {
+            message.raw = Some(raw_message.clone());
         }
-        let json_data = response.json::<serde_json::Value>().await.map_err(|error| {
+        Ok(message)
+    }
+
+    pub fn from_discord_message(channel_id: u64, discord_message: DiscordMessage) -> Self {
+        Self {
+            channel_id,
+            parent_channel_id: None,
+            author_id: discord_message.author.id,
+            message_id: discord_message.id,
+            message: discord_message.content,
+            has_media: !discord_message.attachments.is_empty(),
+            timestamp: discord_message.timestamp,
+            edited_timestamp: discord_message.edited_timestamp,
+            author: Some(discord_message.author),
+            kind: discord_message.kind,
+            flags: discord_message.flags,
+            pinned: discord_message.pinned,
+            tts: discord_message.tts,
+            mention_everyone: discord_message.mention_everyone,
+            mentions: discord_message.mentions,
+            mention_roles: discord_message.mention_roles,
+            attachments: discord_message.attachments,
+            embeds: discord_message.embeds,
+            reactions: discord_message.reactions,
+            message_reference: discord_message.message_reference,
+            sticker_items: discord_message.sticker_items,
+            raw: None,
+        }
+    }
+
+    pub fn reply_to_message_id(&self) -> Option<u64> {
+        self.message_reference
+            .as_ref()
+            .and_then(|message_reference| message_reference.message_id)
+    }
+}
+
+impl DiscordApi {
+    fn process_messages(
+        &self,
+        response: HttpResponse,
+        channel_id: u64,
+    ) -> Result<MessagePage, DiscordApiError> {
+        let json_data = response.json::<serde_json::Value>().map_err(|error| {
             DiscordApiError::ParseResponse(ParseError::DeserializeBodyIntoJson(error))
         })?;
-        let mut messages_vec: Vec<Message> = Vec::new();
-        if let Some(message_array) = json_data.as_array() {
+        let mut message_page = MessagePage::default();
+        if let serde_json::Value::Array(message_array) = json_data {
             for message_object in message_array {
                 let message_id = message_object
                     .get("id")
                     .and_then(|id| id.as_str())
                     .and_then(|id| id.parse::<u64>().ok());
-                let author_id = message_object
-                    .get("author")
-                    .and_then(|author| author.as_object())
-                    .and_then(|author| author.get("id"))
-                    .and_then(|id| id.as_str())
-                    .and_then(|id| id.parse::<u64>().ok());
-                let content = message_object
-                    .get("content")
-                    .and_then(|content| content.as_str())
-                    .map(|s| s.to_string());
-                let has_media = !message_object
-                    .get("attachments")
-                    .and_then(|att| att.as_array())
-                    .map(|arr| arr.is_empty())
-                    .unwrap_or(true);
-                if let (Some(mid), Some(aid), Some(text)) = (message_id, author_id, content) {
-                    messages_vec.push(Message {
-                        channel_id,
-                        author_id: aid,
-                        message_id: mid,
-                        message: text,
-                        has_media,
+                if let Some(message_id) = message_id {
+                    message_page.oldest_message_id = Some(match message_page.oldest_message_id {
+                        Some(oldest_message_id) => oldest_message_id.min(message_id),
+                        None => message_id,
                     });
+                    message_page.newest_message_id =
+                        message_page.newest_message_id.max(Some(message_id));
+                }
+                match Message::from_raw(channel_id, &message_object, self.keep_raw_messages) {
+                    Ok(message) => message_page.messages.push(message),
+                    Err(error) => {
+                        tracing::warn!(
+                            "Rejected message {:?} in channel {}: {}",
+                            message_id,
+                            channel_id,
+                            error
+                        );
+                        message_page.rejected.push(RejectedMessage {
+                            channel_id,
+                            message_id,
+                            reason: error.to_string(),
+                            raw: message_object,
+                        });
+                    }
                 }
             }
         }
-        Ok(messages_vec)
+        Ok(message_page)
     }
 
     pub async fn get_channel_msgs_before_msg(
         &self,
         channel_id: u64,
         message_id: u64,
-        wait_for_ratelimit: bool,
-    ) -> Result<Vec<Message>, DiscordApiError> {
+    ) -> Result<MessagePage, DiscordApiError> {
         let url = format!("channels/{}/messages?before={}&limit=100", channel_id, message_id);
         let response = self.request_with_relative_url_and_auth_header(Method::GET, &url).await?;
         let status = response.status().as_u16();
         match status {
-            200 => Self::process_messages(response, channel_id, wait_for_ratelimit).await,
-            _ => Err(DiscordApiError::UnexpectedResponseStatusCode(status, Some(response))),
+            200 => self.process_messages(response, channel_id),
+            _ => Err(DiscordApiError::UnexpectedResponseStatusCode(
+                status,
+                Some(Box::new(response)),
+            )),
+        }
+    }
+
+    pub async fn get_channel_msgs_after_msg(
+        &self,
+        channel_id: u64,
+        message_id: u64,
+    ) -> Result<MessagePage, DiscordApiError> {
+        let url = format!("channels/{}/messages?after={}&limit=100", channel_id, message_id);
+        let response = self.request_with_relative_url_and_auth_header(Method::GET, &url).await?;
+        let status = response.status().as_u16();
+        match status {
+            200 => self.process_messages(response, channel_id),
+            _ => Err(DiscordApiError::UnexpectedResponseStatusCode(
+                status,
+                Some(Box::new(response)),
+            )),
         }
     }
 
     pub async fn get_channel_msgs(
         &self,
         channel_id: u64,
-        wait_for_ratelimit: bool,
-    ) -> Result<Vec<Message>, DiscordApiError> {
+    ) -> Result<MessagePage, DiscordApiError> {
         let url = format!("channels/{}/messages?limit=100", channel_id);
         let response = self.request_with_relative_url_and_auth_header(Method::GET, &url).await?;
         let status = response.status().as_u16();
         match status {
-            200 => Self::process_messages(response, channel_id, wait_for_ratelimit).await,
-            _ => Err(DiscordApiError::UnexpectedResponseStatusCode(status, Some(response))),
+            200 => self.process_messages(response, channel_id),
+            _ => Err(DiscordApiError::UnexpectedResponseStatusCode(
+                status,
+                Some(Box::new(response)),
+            )),
         }
     }
 }
\ No newline at end of file
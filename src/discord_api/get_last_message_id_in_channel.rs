@@ -5,7 +5,6 @@ impl DiscordApi {
     pub async fn get_last_msg_in_channel(
         &self,
         channel_id: u64,
-        wait_for_ratelimit: bool,
     ) -> Result<(u64, String), DiscordApiError> {
         let response = self
             .request_with_relative_url_and_auth_header(
@@ -18,13 +17,8 @@ impl DiscordApi {
 
         match status {
             200 => {
-                if wait_for_ratelimit {
-                    DiscordApi::handle_rate_limit_wait(response.headers()).await;
-                }
-
                 let json_data = response
                     .json::<serde_json::Value>()
-                    .await
                     .map_err(|error| {
                         DiscordApiError::ParseResponse(ParseError::DeserializeBodyIntoJson(error))
                     })?;
@@ -56,7 +50,7 @@ impl DiscordApi {
             }
             _ => Err(DiscordApiError::UnexpectedResponseStatusCode(
                 status,
-                Some(response),
+                Some(Box::new(response)),
             )),
         }
     }
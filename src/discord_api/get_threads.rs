@@ -0,0 +1,64 @@
+use super::models::{Channel, ThreadList};
+use super::{DiscordApi, DiscordApiError, ParseError};
+use chrono::SecondsFormat;
+use reqwest::Method;
+
+impl DiscordApi {
+    async fn get_thread_list(&self, url: &str) -> Result<ThreadList, DiscordApiError> {
+        let response = self
+            .request_with_relative_url_and_auth_header(Method::GET, url)
+            .await?;
+        let status = response.status().as_u16();
+        match status {
+            200 => response.json::<ThreadList>().map_err(|error| {
+                DiscordApiError::ParseResponse(ParseError::DeserializeBodyIntoJson(error))
+            }),
+            _ => Err(DiscordApiError::UnexpectedResponseStatusCode(
+                status,
+                Some(Box::new(response)),
+            )),
+        }
+    }
+
+    pub async fn get_active_guild_threads(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<Channel>, DiscordApiError> {
+        let url = format!("guilds/{}/threads/active", guild_id);
+        Ok(self.get_thread_list(&url).await?.threads)
+    }
+
+    // Pages through all of them, newest archived first.
+    pub async fn get_archived_threads(
+        &self,
+        channel_id: u64,
+        private: bool,
+    ) -> Result<Vec<Channel>, DiscordApiError> {
+        let visibility = if private { "private" } else { "public" };
+        let mut threads: Vec<Channel> = Vec::new();
+        let mut before = None;
+        loop {
+            let mut url = format!(
+                "channels/{}/threads/archived/{}?limit=100",
+                channel_id, visibility
+            );
+            if let Some(before) = &before {
+                url.push_str(&format!("&before={}", before));
+            }
+            let thread_list = self.get_thread_list(&url).await?;
+            before = thread_list
+                .threads
+                .last()
+                .and_then(|thread| thread.thread_metadata.as_ref())
+                .and_then(|thread_metadata| thread_metadata.archive_timestamp)
+                .map(|archive_timestamp| {
+                    archive_timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+                });
+            threads.extend(thread_list.threads);
+            if !thread_list.has_more || before.is_none() {
+                break;
+            }
+        }
+        Ok(threads)
+    }
+}
@@ -1,15 +1,37 @@
+mod builder;
+pub mod cassette;
+#[cfg(feature = "test-support")]
+mod fake_transport;
+mod get_channel;
 mod get_channel_messages;
+mod get_guild_channels;
+mod get_threads;
 mod get_last_message_id_in_channel;
+pub mod models;
+mod rate_limit;
+pub mod retry;
+pub mod transport;
+
+use rate_limit::{RateLimitedResponse, RateLimiter};
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    Method, StatusCode,
+};
+use transport::{HttpRequest, HttpResponse, Transport, TransportError};
 
-use reqwest::{header, header::HeaderMap, Method, RequestBuilder, Response};
-
-pub use get_channel_messages::Message;
+pub use builder::DiscordApiBuilder;
+#[cfg(feature = "test-support")]
+pub use fake_transport::FakeTransport;
+pub use get_channel_messages::{Message, MessagePage, RejectedMessage};
+pub use retry::RetryPolicy;
 
-const DISCORD_API_BASE_URL: &str = "https://discord.com/api/v9";
+pub const DEFAULT_DISCORD_API_BASE_URL: &str = "https://discord.com/api";
+pub const DEFAULT_DISCORD_API_VERSION: u8 = 9;
 
 use std::{
     fmt,
     fmt::{Display, Formatter},
+    time::Duration,
 };
 
 pub struct DiscordAuth {
@@ -37,8 +59,14 @@ impl Display for DiscordAuth {
 }
 
 pub struct DiscordApi {
-    reqwest_client: reqwest::Client,
+    transport: Box<dyn Transport>,
     auth: DiscordAuth,
+    api_url: String,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    keep_raw_messages: bool,
+    rate_limiter: RateLimiter,
+    retry_policy: RetryPolicy,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -47,13 +75,16 @@ pub enum DiscordApiError {
     NotFound(FoundableStuff),
 
     #[error("Failed to send the request, see {0:#?}")]
-    SendingRequest(reqwest::Error),
+    SendingRequest(TransportError),
 
     #[error(transparent)]
     ParseResponse(ParseError),
 
     #[error("Unexpected status code {0}, see: {1:#?}")]
-    UnexpectedResponseStatusCode(u16, Option<reqwest::Response>),
+    UnexpectedResponseStatusCode(u16, Option<Box<HttpResponse>>),
+
+    #[error("Gave up after {0} attempts, last error: {1}")]
+    RetriesExhausted(u32, Box<DiscordApiError>),
 }
 
 #[allow(dead_code)]
@@ -67,84 +98,105 @@ pub enum FoundableStuff {
 #[derive(Debug, thiserror::Error)]
 pub enum ParseError {
     #[error("Failed to deserialize the response body into json, see: {0:#?}")]
-    DeserializeBodyIntoJson(reqwest::Error),
+    DeserializeBodyIntoJson(serde_json::Error),
 }
 
 impl DiscordApi {
-    pub fn new<S: ToString>(token: S, personal: bool) -> Self {
-        Self {
-            reqwest_client: reqwest::Client::new(),
-            auth: DiscordAuth::new(token, personal),
-        }
+    pub fn builder<S: ToString>(token: S, personal: bool) -> DiscordApiBuilder {
+        DiscordApiBuilder::new(token, personal)
     }
 
-    fn build_request_with_auth_header(&self, method: Method, url: &str) -> RequestBuilder {
-        self.reqwest_client
-            .request(method, url)
-            .header(header::AUTHORIZATION, self.auth.to_string())
+    fn build_request_with_auth_header(
+        &self,
+        method: Method,
+        url: &str,
+    ) -> Result<HttpRequest, DiscordApiError> {
+        let header_value = |value: &str| {
+            HeaderValue::from_str(value)
+                .map_err(|error| DiscordApiError::SendingRequest(error.into()))
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, header_value(&self.auth.to_string())?);
+        if let Some(user_agent) = &self.user_agent {
+            headers.insert(header::USER_AGENT, header_value(user_agent)?);
+        }
+        Ok(HttpRequest {
+            method,
+            url: url.to_string(),
+            headers,
+            timeout: self.timeout,
+        })
     }
 
     async fn request_with_relative_url_and_auth_header(
         &self,
         method: Method,
         relative_url: &str,
-    ) -> Result<Response, DiscordApiError> {
-        self.build_request_with_auth_header(
-            method,
-            &format!("{}/{}", DISCORD_API_BASE_URL, relative_url),
-        )
-            .send()
-            .await
-            .map_err(DiscordApiError::SendingRequest)
-    }
-
-    fn get_ratelimit_info_from_response_header_map(
-        header_map: &HeaderMap,
-    ) -> Option<DiscordApiRatelimitInfo> {
-        let ratelimit_remaining = header_map
-            .get("X-RateLimit-Remaining")
-            .and_then(|string| string.to_str().ok())
-            .and_then(|string| string.parse::<u8>().ok());
-
-        let rate_limit_reset_after = header_map
-            .get("X-RateLimit-Reset-After")
-            .and_then(|string| string.to_str().ok())
-            .and_then(|string| string.parse::<f32>().ok());
-
-        if let (Some(ratelimit_remaining), Some(rate_limit_reset_after)) =
-            (ratelimit_remaining, rate_limit_reset_after)
-        {
-            let milliseconds_until_ratelimit_reset =
-                (rate_limit_reset_after * 1000_f32).ceil() as u16;
-            return Some(DiscordApiRatelimitInfo {
-                remaining_limit: ratelimit_remaining,
-                milliseconds_until_ratelimit_reset,
-            });
-        }
-
-        None
-    }
-
-    async fn handle_rate_limit_wait(header_map_with_rate_limit_info: &HeaderMap) {
-        if let Some(ratelimit_info) =
-            DiscordApi::get_ratelimit_info_from_response_header_map(header_map_with_rate_limit_info)
-        {
-            if ratelimit_info.remaining_limit == 0 {
-                tracing::info!(
-                    "Waiting {} milliseconds to respect the rate limit.",
-                    ratelimit_info.milliseconds_until_ratelimit_reset
-                );
-                tokio::time::sleep(tokio::time::Duration::from_millis(
-                    ratelimit_info.milliseconds_until_ratelimit_reset.into(),
-                ))
-                    .await;
+    ) -> Result<HttpResponse, DiscordApiError> {
+        let route = RateLimiter::route_key(&method, relative_url);
+        let url = format!("{}/{}", self.api_url, relative_url);
+        let request = self.build_request_with_auth_header(method, &url)?;
+        let mut attempts_made = 0;
+        let mut rate_limited_attempts = 0;
+        loop {
+            self.rate_limiter.acquire(&route).await;
+            let error = match self.transport.send(request.clone()).await {
+                Err(error) => DiscordApiError::SendingRequest(error),
+                // Rate limits are waited out whatever the retryable status codes, and count
+                // towards `max_rate_limited_attempts` rather than `max_attempts`: the rate limiter
+                // holds the next request back for as long as Discord asked.
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    self.rate_limiter.update(&route, response.headers()).await;
+                    let rate_limited_response = RateLimitedResponse::from_response(&response);
+                    self.rate_limiter.block(&route, &rate_limited_response).await;
+                    rate_limited_attempts += 1;
+                    if !self
+                        .retry_policy
+                        .has_rate_limited_attempts_left(rate_limited_attempts)
+                    {
+                        return Err(DiscordApiError::RetriesExhausted(
+                            rate_limited_attempts,
+                            Box::new(DiscordApiError::UnexpectedResponseStatusCode(
+                                response.status().as_u16(),
+                                Some(Box::new(response)),
+                            )),
+                        ));
+                    }
+                    tracing::warn!(
+                        "Hit the rate limit of `{}` (global: {}), retrying in {} milliseconds.",
+                        route,
+                        rate_limited_response.global,
+                        rate_limited_response.retry_after.as_millis()
+                    );
+                    continue;
+                }
+                Ok(response) => {
+                    self.rate_limiter.update(&route, response.headers()).await;
+                    let status = response.status();
+                    if !self.retry_policy.is_retryable_status_code(status.as_u16()) {
+                        return Ok(response);
+                    }
+                    DiscordApiError::UnexpectedResponseStatusCode(
+                        status.as_u16(),
+                        Some(Box::new(response)),
+                    )
+                }
+            };
+
+            attempts_made += 1;
+            if !self.retry_policy.has_attempts_left(attempts_made) {
+                return Err(DiscordApiError::RetriesExhausted(attempts_made, Box::new(error)));
             }
+            let backoff = self.retry_policy.backoff(attempts_made);
+            tracing::warn!(
+                "Attempt {}/{} of `{}` failed, retrying in {} milliseconds. See: {}",
+                attempts_made,
+                self.retry_policy.max_attempts,
+                route,
+                backoff.as_millis(),
+                error
+            );
+            tokio::time::sleep(backoff).await;
         }
     }
 }
-
-#[derive(Debug)]
-struct DiscordApiRatelimitInfo {
-    remaining_limit: u8,
-    milliseconds_until_ratelimit_reset: u16,
-}
\ No newline at end of file
@@ -0,0 +1,292 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+// Typed counterparts of the objects returned by the Discord API, see
+// https://discord.com/developers/docs/resources/message. Every field that Discord may omit is
+// defaulted so a missing optional field never makes a whole message unparseable.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscordMessage {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub channel_id: Option<u64>,
+    pub author: User,
+    pub content: String,
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub edited_timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tts: bool,
+    #[serde(default)]
+    pub mention_everyone: bool,
+    #[serde(default)]
+    pub mentions: Vec<User>,
+    #[serde(default, deserialize_with = "snowflakes")]
+    pub mention_roles: Vec<u64>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, rename = "type")]
+    pub kind: u8,
+    #[serde(default)]
+    pub flags: u64,
+    #[serde(default)]
+    pub message_reference: Option<MessageReference>,
+    #[serde(default)]
+    pub sticker_items: Vec<Sticker>,
+}
+
+// Values of the `type` field of a channel.
+pub mod channel_kind {
+    pub const GUILD_TEXT: u8 = 0;
+    pub const GUILD_VOICE: u8 = 2;
+    pub const GUILD_CATEGORY: u8 = 4;
+    pub const GUILD_ANNOUNCEMENT: u8 = 5;
+    pub const GUILD_STAGE_VOICE: u8 = 13;
+    pub const GUILD_FORUM: u8 = 15;
+    pub const GUILD_MEDIA: u8 = 16;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Channel {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub kind: u8,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub guild_id: Option<u64>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub parent_id: Option<u64>,
+    #[serde(default)]
+    pub position: i32,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub last_message_id: Option<u64>,
+    #[serde(default)]
+    pub thread_metadata: Option<ThreadMetadata>,
+}
+
+impl Channel {
+    // Forum and media channels have no messages of their own, only posts which are threads.
+    pub fn is_forum(&self) -> bool {
+        self.kind == channel_kind::GUILD_FORUM || self.kind == channel_kind::GUILD_MEDIA
+    }
+
+    // Threads can only be started in text, announcement, forum and media channels, the thread
+    // listings fail for the others such as DMs and voice channels.
+    pub fn has_threads(&self) -> bool {
+        self.kind == channel_kind::GUILD_TEXT
+            || self.kind == channel_kind::GUILD_ANNOUNCEMENT
+            || self.is_forum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadMetadata {
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub archive_timestamp: Option<DateTime<Utc>>,
+}
+
+// Body of the thread listing endpoints.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThreadList {
+    pub threads: Vec<Channel>,
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub global_name: Option<String>,
+    #[serde(default)]
+    pub discriminator: Option<String>,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub bot: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    #[serde(default)]
+    pub filename: String,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Embed {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub color: Option<u32>,
+    #[serde(default)]
+    pub footer: Option<EmbedFooter>,
+    #[serde(default)]
+    pub image: Option<EmbedMedia>,
+    #[serde(default)]
+    pub thumbnail: Option<EmbedMedia>,
+    #[serde(default)]
+    pub video: Option<EmbedMedia>,
+    #[serde(default)]
+    pub provider: Option<EmbedProvider>,
+    #[serde(default)]
+    pub author: Option<EmbedAuthor>,
+    #[serde(default)]
+    pub fields: Vec<EmbedField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedFooter {
+    pub text: String,
+    #[serde(default)]
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedMedia {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedProvider {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedAuthor {
+    pub name: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub inline: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reaction {
+    pub count: u32,
+    #[serde(default)]
+    pub me: bool,
+    pub emoji: Emoji,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Emoji {
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub id: Option<u64>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub animated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageReference {
+    #[serde(default, rename = "type")]
+    pub kind: u8,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub message_id: Option<u64>,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub channel_id: Option<u64>,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub guild_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sticker {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub format_type: u8,
+}
+
+// Discord sends snowflakes as strings, while the archives written by this crate store them as
+// numbers, so both representations are accepted.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnowflakeRepr {
+    String(String),
+    Number(u64),
+}
+
+impl SnowflakeRepr {
+    fn into_u64<E: serde::de::Error>(self) -> Result<u64, E> {
+        match self {
+            SnowflakeRepr::String(string) => string.parse::<u64>().map_err(E::custom),
+            SnowflakeRepr::Number(number) => Ok(number),
+        }
+    }
+}
+
+fn snowflake<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    SnowflakeRepr::deserialize(deserializer)?.into_u64()
+}
+
+fn optional_snowflake<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Option::<SnowflakeRepr>::deserialize(deserializer)?
+        .map(SnowflakeRepr::into_u64)
+        .transpose()
+}
+
+fn snowflakes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u64>, D::Error> {
+    Vec::<SnowflakeRepr>::deserialize(deserializer)?
+        .into_iter()
+        .map(SnowflakeRepr::into_u64)
+        .collect()
+}
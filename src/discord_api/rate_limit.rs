@@ -0,0 +1,200 @@
+use super::transport::HttpResponse;
+use reqwest::{header::HeaderMap, Method};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::{sync::Mutex, time::Instant};
+
+// Discord keys its buckets by the bucket hash plus the "major parameter" of the route, which is
+// the channel, guild or webhook id right after the first path segment.
+const MAJOR_PARAMETER_ROUTES: [&str; 3] = ["channels", "guilds", "webhooks"];
+
+#[derive(Default)]
+pub struct RateLimiter {
+    state: Mutex<RateLimiterState>,
+}
+
+#[derive(Default)]
+struct RateLimiterState {
+    route_buckets: HashMap<String, String>,
+    buckets: HashMap<String, Bucket>,
+    global_reset_at: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    remaining: u32,
+    reset_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct RateLimitedResponse {
+    pub retry_after: Duration,
+    pub global: bool,
+}
+
+#[derive(Deserialize)]
+struct RateLimitedResponseBody {
+    retry_after: Option<f64>,
+    #[serde(default)]
+    global: bool,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route_key(method: &Method, relative_url: &str) -> String {
+        let path = relative_url.split('?').next().unwrap_or(relative_url);
+        format!("{} {}", method, path)
+    }
+
+    fn major_parameter(route: &str) -> &str {
+        let path = route.split(' ').nth(1).unwrap_or(route);
+        let mut segments = path.split('/');
+        match (segments.next(), segments.next()) {
+            (Some(resource), Some(id)) if MAJOR_PARAMETER_ROUTES.contains(&resource) => id,
+            _ => "",
+        }
+    }
+
+    fn bucket_key(route: &str, bucket_hash: &str) -> String {
+        format!("{}:{}", bucket_hash, Self::major_parameter(route))
+    }
+
+    fn time_until(instant: Instant) -> Option<Duration> {
+        let now = Instant::now();
+        (instant > now).then(|| instant - now)
+    }
+
+    // Waits until both the global limit and the bucket of the route allow another request, and
+    // reserves one slot of the bucket so concurrent requests don't overrun it.
+    pub async fn acquire(&self, route: &str) {
+        loop {
+            let time_to_wait = {
+                let mut state = self.state.lock().await;
+                let global_wait = state.global_reset_at.and_then(Self::time_until);
+                if global_wait.is_none() {
+                    state.global_reset_at = None;
+                }
+                let bucket_key = state
+                    .route_buckets
+                    .get(route)
+                    .map(|bucket_hash| Self::bucket_key(route, bucket_hash));
+                let bucket_wait = match bucket_key.and_then(|key| state.buckets.get_mut(&key)) {
+                    Some(bucket) => match Self::time_until(bucket.reset_at) {
+                        Some(reset_in) if bucket.remaining == 0 => Some(reset_in),
+                        Some(_) => {
+                            if global_wait.is_none() {
+                                bucket.remaining -= 1;
+                            }
+                            None
+                        }
+                        None => None,
+                    },
+                    None => None,
+                };
+                global_wait.or(bucket_wait)
+            };
+            match time_to_wait {
+                Some(duration) => {
+                    tracing::info!(
+                        "Waiting {} milliseconds to respect the rate limit.",
+                        duration.as_millis()
+                    );
+                    tokio::time::sleep(duration).await;
+                }
+                None => return,
+            }
+        }
+    }
+
+    pub async fn update(&self, route: &str, header_map: &HeaderMap) {
+        let header_value = |name: &str| header_map.get(name).and_then(|value| value.to_str().ok());
+        let bucket_hash = header_value("X-RateLimit-Bucket");
+        let remaining =
+            header_value("X-RateLimit-Remaining").and_then(|value| value.parse::<u32>().ok());
+        let reset_after =
+            header_value("X-RateLimit-Reset-After").and_then(|value| value.parse::<f64>().ok());
+
+        if let (Some(bucket_hash), Some(remaining), Some(reset_after)) =
+            (bucket_hash, remaining, reset_after)
+        {
+            let mut state = self.state.lock().await;
+            state
+                .route_buckets
+                .insert(route.to_string(), bucket_hash.to_string());
+            state.buckets.insert(
+                Self::bucket_key(route, bucket_hash),
+                Bucket {
+                    remaining,
+                    reset_at: Instant::now() + Duration::from_secs_f64(reset_after.max(0.0)),
+                },
+            );
+        }
+    }
+
+    pub async fn block(&self, route: &str, rate_limited_response: &RateLimitedResponse) {
+        let reset_at = Instant::now() + rate_limited_response.retry_after;
+        let mut state = self.state.lock().await;
+        if rate_limited_response.global {
+            state.global_reset_at = Some(reset_at);
+            return;
+        }
+        if let Some(bucket_key) = state
+            .route_buckets
+            .get(route)
+            .map(|bucket_hash| Self::bucket_key(route, bucket_hash))
+        {
+            state.buckets.insert(
+                bucket_key,
+                Bucket {
+                    remaining: 0,
+                    reset_at,
+                },
+            );
+        } else {
+            // The bucket isn't known yet, use the route itself as one so the next attempt waits.
+            state
+                .route_buckets
+                .insert(route.to_string(), route.to_string());
+            state.buckets.insert(
+                Self::bucket_key(route, route),
+                Bucket {
+                    remaining: 0,
+                    reset_at,
+                },
+            );
+        }
+    }
+}
+
+impl RateLimitedResponse {
+    // Prefers the `retry_after` of the JSON body over the `Retry-After` header since the former
+    // has millisecond precision.
+    pub fn from_response(response: &HttpResponse) -> Self {
+        let header_map = response.headers();
+        let header_retry_after = header_map
+            .get("Retry-After")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<f64>().ok());
+        let header_global = header_map
+            .get("X-RateLimit-Global")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+        let body = response.json::<RateLimitedResponseBody>().ok();
+
+        let retry_after = body
+            .as_ref()
+            .and_then(|body| body.retry_after)
+            .or(header_retry_after)
+            .unwrap_or(1.0);
+        let global = header_global || body.is_some_and(|body| body.global);
+
+        Self {
+            retry_after: Duration::from_secs_f64(retry_after.max(0.0)),
+            global,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use rand::Rng;
+use std::time::Duration;
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+pub const DEFAULT_INITIAL_BACKOFF_MS: u64 = 1000;
+pub const DEFAULT_MAX_BACKOFF_MS: u64 = 60_000;
+// 429s aren't listed, rate limits are waited out up to their own number of attempts.
+pub const DEFAULT_RETRYABLE_STATUS_CODES: [u16; 4] = [500, 502, 503, 504];
+pub const DEFAULT_MAX_RATE_LIMITED_ATTEMPTS: u32 = 20;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub retryable_status_codes: Vec<u16>,
+    // Rate limited responses of a request, counted apart from `max_attempts` since waiting out a
+    // rate limit isn't a failure, but a request that keeps getting them still gives up.
+    pub max_rate_limited_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(DEFAULT_INITIAL_BACKOFF_MS),
+            max_backoff: Duration::from_millis(DEFAULT_MAX_BACKOFF_MS),
+            retryable_status_codes: DEFAULT_RETRYABLE_STATUS_CODES.to_vec(),
+            max_rate_limited_attempts: DEFAULT_MAX_RATE_LIMITED_ATTEMPTS,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_retryable_status_code(&self, status_code: u16) -> bool {
+        self.retryable_status_codes.contains(&status_code)
+    }
+
+    pub fn has_attempts_left(&self, attempts_made: u32) -> bool {
+        attempts_made < self.max_attempts
+    }
+
+    pub fn has_rate_limited_attempts_left(&self, rate_limited_attempts: u32) -> bool {
+        rate_limited_attempts < self.max_rate_limited_attempts
+    }
+
+    // Exponential backoff with "equal jitter": half of the delay is fixed and the other half is
+    // random, so concurrent scrapes that failed together don't retry in lockstep.
+    pub fn backoff(&self, attempts_made: u32) -> Duration {
+        let exponent = attempts_made.saturating_sub(1).min(31);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let half_delay = delay / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half_delay.as_millis() as u64);
+        half_delay + Duration::from_millis(jitter)
+    }
+}
@@ -1,7 +1,14 @@
 mod cli;
 pub use cli::run as run_cli;
 
-mod discord_api;
-mod scraper;
+pub mod discord_api;
+#[cfg(feature = "test-support")]
+pub mod mock_server;
+pub mod scraper;
+pub mod transcript;
 
-mod utils;
\ No newline at end of file
+pub mod utils;
+
+pub use discord_api::{DiscordApi, DiscordApiBuilder, DiscordApiError, Message};
+pub use scraper::{ParseMode, ScrapeBounds, Scraper, ScraperError};
+pub use utils::message_saver::{MessageSaver, SaveTarget};
\ No newline at end of file
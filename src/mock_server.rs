@@ -0,0 +1,430 @@
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+// A local stand-in for the endpoints the scraper uses, backed by fixture JSON files, so scrapes
+// can be exercised end to end by pointing `--api_base_url` at it.
+//
+// A request is answered with the fixture at its path, e.g. `GET /api/v9/channels/1` serves
+// `<fixtures_dir>/channels/1.json`. Listings are paginated out of full lists instead:
+// `channels/<id>/messages.json` for messages (`before`, `after`, `around` and `limit`) and
+// `channels/<id>/threads/archived/<public|private>.json` for archived threads (`before` and
+// `limit`). Thread listings without a fixture are empty.
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct MockServerConfig {
+    pub fixtures_dir: PathBuf,
+    pub address: SocketAddr,
+    // Caps the `limit` of listings, so small fixtures still span several pages.
+    pub max_page_size: usize,
+    // Requests allowed per route until the window of `rate_limit_reset_after` resets, announced
+    // through the `X-RateLimit-*` headers. Requests over it are answered with a 429.
+    pub rate_limit: Option<u32>,
+    pub rate_limit_reset_after: Duration,
+    // Answers every n-th request with a 429, respectively a 503, whatever the rate limit says.
+    pub too_many_requests_every: Option<u64>,
+    pub service_unavailable_every: Option<u64>,
+}
+
+impl MockServerConfig {
+    // Listens on a free port of the loopback interface.
+    pub fn new<P: Into<PathBuf>>(fixtures_dir: P) -> Self {
+        Self {
+            fixtures_dir: fixtures_dir.into(),
+            address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            max_page_size: MAX_LIMIT,
+            rate_limit: None,
+            rate_limit_reset_after: Duration::from_secs(1),
+            too_many_requests_every: None,
+            service_unavailable_every: None,
+        }
+    }
+}
+
+pub struct MockDiscordServer {
+    address: SocketAddr,
+    accept_task: JoinHandle<()>,
+}
+
+impl MockDiscordServer {
+    pub async fn start(config: MockServerConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(config.address).await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(MockState {
+            config,
+            request_count: AtomicU64::new(0),
+            buckets: Mutex::new(HashMap::new()),
+        });
+        let accept_task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(error) => {
+                        tracing::warn!("Mock server failed to accept a connection: {}", error);
+                        continue;
+                    }
+                };
+                let state = Arc::clone(&state);
+                tokio::spawn(async move {
+                    if let Err(error) = state.handle_connection(stream).await {
+                        tracing::warn!("Mock server failed to answer a request: {}", error);
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            address,
+            accept_task,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // What to pass as `--api_base_url`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}/api", self.address)
+    }
+
+    // Serves until the process is stopped.
+    pub async fn wait(&mut self) {
+        let _ = (&mut self.accept_task).await;
+    }
+}
+
+impl Drop for MockDiscordServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+struct MockState {
+    config: MockServerConfig,
+    request_count: AtomicU64,
+    buckets: Mutex<HashMap<String, MockBucket>>,
+}
+
+struct MockBucket {
+    used: u32,
+    reset_at: Instant,
+}
+
+struct MockResponse {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+impl MockResponse {
+    fn new(status: StatusCode, body: Value) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    fn error(status: StatusCode, message: &str, code: u32) -> Self {
+        Self::new(status, json!({ "message": message, "code": code }))
+    }
+
+    fn not_found() -> Self {
+        Self::error(StatusCode::NOT_FOUND, "404: Not Found", 0)
+    }
+
+    fn rate_limited(retry_after: Duration) -> Self {
+        let mut response = Self::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            json!({
+                "message": "You are being rate limited.",
+                "retry_after": retry_after.as_secs_f64(),
+                "global": false,
+            }),
+        );
+        response.headers.push((
+            "Retry-After".to_string(),
+            retry_after.as_secs_f64().ceil().to_string(),
+        ));
+        response
+    }
+}
+
+impl MockState {
+    async fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+        loop {
+            let mut header_line = String::new();
+            if reader.read_line(&mut header_line).await? == 0 || header_line.trim().is_empty() {
+                break;
+            }
+        }
+        let mut request_line_parts = request_line.split_whitespace();
+        let method = request_line_parts.next().unwrap_or_default();
+        let target = request_line_parts.next().unwrap_or_default();
+        let response = self.respond(method, target);
+
+        let body = response.body.to_string();
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            response.status.as_u16(),
+            response.status.canonical_reason().unwrap_or_default(),
+            body.len()
+        );
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        let mut stream = reader.into_inner();
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    fn respond(&self, method: &str, target: &str) -> MockResponse {
+        let request_number = self.request_count.fetch_add(1, Ordering::SeqCst) + 1;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let Some(route) = Self::route(path) else {
+            return MockResponse::not_found();
+        };
+        if method != "GET" {
+            return MockResponse::error(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "405: Method Not Allowed",
+                0,
+            );
+        }
+        let is_nth = |every: Option<u64>| every.is_some_and(|every| request_number % every == 0);
+        if is_nth(self.config.service_unavailable_every) {
+            return MockResponse::error(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable", 0);
+        }
+        if is_nth(self.config.too_many_requests_every) {
+            return MockResponse::rate_limited(self.config.rate_limit_reset_after);
+        }
+        let rate_limit_headers = match self.take_rate_limit_slot(route) {
+            Ok(rate_limit_headers) => rate_limit_headers,
+            Err(response) => return response,
+        };
+        let query = Self::parse_query(query);
+        let mut response = self.serve(route, &query);
+        response.headers.extend(rate_limit_headers);
+        response
+    }
+
+    // `/api/v9/channels/1` -> `channels/1`
+    fn route(path: &str) -> Option<&str> {
+        let (version, route) = path.strip_prefix("/api/v")?.split_once('/')?;
+        version.parse::<u8>().ok()?;
+        Some(route.trim_end_matches('/'))
+    }
+
+    fn parse_query(query: &str) -> HashMap<String, String> {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), Self::percent_decode(value)))
+            .collect()
+    }
+
+    fn percent_decode(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut index = 0;
+        while index < bytes.len() {
+            let hex = bytes
+                .get(index + 1..index + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (bytes[index], hex) {
+                (b'%', Some(byte)) => {
+                    decoded.push(byte);
+                    index += 3;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    index += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
+
+    fn take_rate_limit_slot(&self, route: &str) -> Result<Vec<(String, String)>, MockResponse> {
+        let Some(limit) = self.config.rate_limit else {
+            return Ok(Vec::new());
+        };
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(route.to_string()).or_insert(MockBucket {
+            used: 0,
+            reset_at: now + self.config.rate_limit_reset_after,
+        });
+        if bucket.reset_at <= now {
+            bucket.used = 0;
+            bucket.reset_at = now + self.config.rate_limit_reset_after;
+        }
+        let reset_after = bucket.reset_at - now;
+        if bucket.used >= limit {
+            return Err(MockResponse::rate_limited(reset_after));
+        }
+        bucket.used += 1;
+        Ok(vec![
+            ("X-RateLimit-Limit".to_string(), limit.to_string()),
+            (
+                "X-RateLimit-Remaining".to_string(),
+                (limit - bucket.used).to_string(),
+            ),
+            (
+                "X-RateLimit-Reset-After".to_string(),
+                format!("{:.3}", reset_after.as_secs_f64()),
+            ),
+            ("X-RateLimit-Bucket".to_string(), format!("mock-{}", route)),
+        ])
+    }
+
+    fn serve(&self, route: &str, query: &HashMap<String, String>) -> MockResponse {
+        let segments: Vec<&str> = route.split('/').collect();
+        match segments.as_slice() {
+            ["channels", channel_id, "messages"] => match self.fixture(route) {
+                Some(messages) => self.messages_page(messages, query),
+                None if self.fixture(&format!("channels/{}", channel_id)).is_some() => {
+                    MockResponse::new(StatusCode::OK, json!([]))
+                }
+                None => MockResponse::error(StatusCode::NOT_FOUND, "Unknown Channel", 10003),
+            },
+            ["channels", _, "threads", "archived", "public" | "private"] => {
+                let threads = self.fixture(route).unwrap_or_else(|| json!([]));
+                self.archived_threads_page(threads, query)
+            }
+            ["guilds", _, "threads", "active"] => MockResponse::new(
+                StatusCode::OK,
+                self.fixture(route)
+                    .unwrap_or_else(|| json!({ "threads": [], "members": [] })),
+            ),
+            _ => match self.fixture(route) {
+                Some(fixture) => MockResponse::new(StatusCode::OK, fixture),
+                None => MockResponse::not_found(),
+            },
+        }
+    }
+
+    fn fixture(&self, route: &str) -> Option<Value> {
+        let path = self.config.fixtures_dir.join(format!("{}.json", route));
+        let fixture = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&fixture) {
+            Ok(fixture) => Some(fixture),
+            Err(error) => {
+                tracing::warn!("Skipping invalid fixture `{}`: {}", path.display(), error);
+                None
+            }
+        }
+    }
+
+    fn limit(&self, query: &HashMap<String, String>) -> usize {
+        query
+            .get("limit")
+            .and_then(|limit| limit.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_LIMIT)
+            .clamp(1, MAX_LIMIT)
+            .min(self.config.max_page_size.max(1))
+    }
+
+    // Newest first like Discord, whatever the order of the fixture.
+    fn messages_page(&self, messages: Value, query: &HashMap<String, String>) -> MockResponse {
+        let mut messages: Vec<(u64, Value)> = messages
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|message| Some((Self::snowflake(message.get("id")?)?, message.clone())))
+            .collect();
+        messages.sort_by(|(id, _), (other_id, _)| other_id.cmp(id));
+        let limit = self.limit(query);
+        let query_id = |key: &str| query.get(key).and_then(|id| id.parse::<u64>().ok());
+
+        let page: Vec<Value> = if let Some(before) = query_id("before") {
+            messages
+                .into_iter()
+                .filter(|(id, _)| *id < before)
+                .take(limit)
+                .map(|(_, message)| message)
+                .collect()
+        } else if let Some(after) = query_id("after") {
+            let newer: Vec<Value> = messages
+                .into_iter()
+                .filter(|(id, _)| *id > after)
+                .map(|(_, message)| message)
+                .collect();
+            newer[newer.len().saturating_sub(limit)..].to_vec()
+        } else if let Some(around) = query_id("around") {
+            let position = messages
+                .iter()
+                .position(|(id, _)| *id <= around)
+                .unwrap_or(messages.len());
+            messages
+                .into_iter()
+                .skip(position.saturating_sub(limit / 2))
+                .take(limit)
+                .map(|(_, message)| message)
+                .collect()
+        } else {
+            messages
+                .into_iter()
+                .take(limit)
+                .map(|(_, message)| message)
+                .collect()
+        };
+        MockResponse::new(StatusCode::OK, Value::Array(page))
+    }
+
+    // Most recently archived first, paginated by archive timestamp.
+    fn archived_threads_page(
+        &self,
+        threads: Value,
+        query: &HashMap<String, String>,
+    ) -> MockResponse {
+        let archive_timestamp = |thread: &Value| {
+            thread
+                .pointer("/thread_metadata/archive_timestamp")
+                .and_then(Value::as_str)
+                .and_then(|timestamp| timestamp.parse::<DateTime<Utc>>().ok())
+        };
+        let mut threads: Vec<Value> = threads.as_array().cloned().unwrap_or_default();
+        threads.sort_by_key(|thread| std::cmp::Reverse(archive_timestamp(thread)));
+        if let Some(before) = query
+            .get("before")
+            .and_then(|before| before.parse::<DateTime<Utc>>().ok())
+        {
+            threads.retain(|thread| archive_timestamp(thread).is_some_and(|at| at < before));
+        }
+        let limit = self.limit(query);
+        let has_more = threads.len() > limit;
+        threads.truncate(limit);
+        MockResponse::new(
+            StatusCode::OK,
+            json!({ "threads": threads, "members": [], "has_more": has_more }),
+        )
+    }
+
+    fn snowflake(value: &Value) -> Option<u64> {
+        match value {
+            Value::String(string) => string.parse().ok(),
+            value => value.as_u64(),
+        }
+    }
+}
@@ -1,19 +1,125 @@
-use crate::discord_api::{DiscordApi, DiscordApiError};
-use crate::utils::message_saver::{JsonlSaver, MessageSaver, SaveTarget, SqlSaver};
-use async_recursion::async_recursion;
+use crate::discord_api::models::Channel;
+use crate::discord_api::{DiscordApi, DiscordApiError, Message, MessagePage, RejectedMessage};
+use crate::transcript::{html, text, Transcript, TranscriptFormat};
+use crate::utils::channel_filter::ChannelFilter;
+use crate::utils::checkpoint::ScrapeCheckpoint;
+use crate::utils::compression;
+use crate::utils::csv_saver::{CsvOptions, CsvSaver};
+use crate::utils::message_saver::{
+    ConnectedSaveTarget, MessageSaver, SaveTarget, ScrapeRunOutcome,
+};
+use crate::utils::parquet_saver::ParquetSaver;
+use crate::utils::snowflake::snowflake_from_datetime;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre;
+use futures::stream::{self, Stream, StreamExt};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io;
 use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::{
     fs::File,
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    time,
+    io::{AsyncBufReadExt, AsyncWriteExt},
 };
 
-const SECONDS_TO_WAIT_IN_CASE_OF_HTTP_503: u8 = 20;
-
 pub struct Scraper {
     discord_api_client: DiscordApi,
+    parse_mode: ParseMode,
+    resume: bool,
+    incremental: bool,
+    include_threads: bool,
+    bounds: ScrapeBounds,
+    // Active threads are listed per guild, so they're only fetched once for all of its channels.
+    active_guild_threads: tokio::sync::Mutex<HashMap<u64, Arc<Vec<Channel>>>>,
+}
+
+// Exclusive message id bounds of a scrape, `None` meaning unbounded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScrapeBounds {
+    pub after_message_id: Option<u64>,
+    pub before_message_id: Option<u64>,
+}
+
+impl ScrapeBounds {
+    // `since` is inclusive and `until` exclusive, when both a date and an id bound a side the
+    // tighter one wins.
+    pub fn new(
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        after_message_id: Option<u64>,
+        before_message_id: Option<u64>,
+    ) -> Self {
+        let after_since = since.map(|since| snowflake_from_datetime(since).saturating_sub(1));
+        let before_until = until.map(snowflake_from_datetime);
+        Self {
+            after_message_id: after_message_id.max(after_since),
+            before_message_id: match (before_message_id, before_until) {
+                (Some(before_message_id), Some(before_until)) => {
+                    Some(before_message_id.min(before_until))
+                }
+                (before_message_id, before_until) => before_message_id.or(before_until),
+            },
+        }
+    }
+
+    fn is_after_lower_bound(&self, message_id: u64) -> bool {
+        self.after_message_id
+            .is_none_or(|after_message_id| message_id > after_message_id)
+    }
+
+    fn is_before_upper_bound(&self, message_id: u64) -> bool {
+        self.before_message_id
+            .is_none_or(|before_message_id| message_id < before_message_id)
+    }
+}
+
+// Lenient skips (and quarantines) what can't be parsed, strict aborts on the first such item.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+#[derive(Debug)]
+pub struct ChannelScrapeSummary {
+    pub channel_id: u64,
+    pub channel_name: String,
+    pub output_path: Option<PathBuf>,
+    pub duration: Duration,
+    pub saved_messages: u64,
+    pub scraped_threads: u64,
+    pub rejected_messages: u64,
+    pub rejection_reasons: BTreeMap<String, u64>,
+}
+
+impl ChannelScrapeSummary {
+    fn new(channel_id: u64, channel_name: String) -> Self {
+        Self {
+            channel_id,
+            channel_name,
+            output_path: None,
+            duration: Duration::ZERO,
+            saved_messages: 0,
+            scraped_threads: 0,
+            rejected_messages: 0,
+            rejection_reasons: BTreeMap::new(),
+        }
+    }
+
+    fn record_rejected(&mut self, rejected_messages: &[RejectedMessage]) {
+        self.rejected_messages += rejected_messages.len() as u64;
+        for rejected_message in rejected_messages {
+            *self
+                .rejection_reasons
+                .entry(rejected_message.reason.clone())
+                .or_default() += 1;
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -22,6 +128,18 @@ pub enum ScraperError {
     DiscordApiError(DiscordApiError),
     #[error("Failed to save messages: {0}")]
     SaveError(#[from] color_eyre::eyre::Error),
+    #[error("Rejected {0} message(s) of channel {1} in strict mode, first reason: {2}")]
+    RejectedMessages(usize, u64, String),
+}
+
+impl ScraperError {
+    // HTTP 403, what Discord answers for channels the bot isn't allowed to read.
+    pub fn is_missing_access(&self) -> bool {
+        matches!(
+            self,
+            ScraperError::DiscordApiError(DiscordApiError::UnexpectedResponseStatusCode(403, _))
+        )
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -36,6 +154,10 @@ pub enum FileConversionError {
     CreateOutputFile(PathBuf, io::Error),
     #[error("Failed to serialize the items from jsonl into json, see: {0:#?}")]
     SerializeJsonlItems(serde_json::Error),
+    #[error("Line {1} of `{0}` isn't valid JSON, see: {2}")]
+    InvalidLine(PathBuf, u64, serde_json::Error),
+    #[error("Failed to save the messages into `{0}`, see: {1:#?}")]
+    SaveMessages(PathBuf, color_eyre::eyre::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -47,135 +169,568 @@ pub enum InvalidPathError {
 }
 
 impl Scraper {
-    pub fn new<S: ToString>(bot_token: S, personal: bool) -> Self {
+    pub fn new(discord_api_client: DiscordApi) -> Self {
         Self {
-            discord_api_client: DiscordApi::new(bot_token, personal),
-        }
-    }
-    async fn get_channel_info(&self, channel_id: u64) -> Result<(u64, String), ScraperError> {
-        match self.discord_api_client.get_last_msg_in_channel(channel_id, true).await {
-            Ok(data) => Ok(data),
-            Err(e) => {
-                if e.to_string().contains("ChannelName") {
-                    tracing::warn!(
-                        "Channel name not found for channel {}: falling back to DM mode.",
-                        channel_id
-                    );
-                    let channel_last_msg_id = match self.discord_api_client.get_last_msg_in_channel(channel_id, false).await {
-                        Ok((id, _)) => id,
-                        Err(_) => 0,
-                    };
-                    Ok((channel_last_msg_id, format!("dm_{}", channel_id)))
-                } else {
-                    Err(ScraperError::DiscordApiError(e))
-                }
-            }
+            discord_api_client,
+            parse_mode: ParseMode::default(),
+            resume: false,
+            incremental: false,
+            include_threads: false,
+            bounds: ScrapeBounds::default(),
+            active_guild_threads: tokio::sync::Mutex::new(HashMap::new()),
         }
     }
 
-    #[async_recursion]
+    pub fn with_bounds(mut self, bounds: ScrapeBounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    // Continue from the checkpoint of the previous scrape of a channel instead of starting over.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    // Only fetch the messages newer than the newest one already archived.
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    // Also scrape the threads (and forum posts) of every channel into the channel's output.
+    pub fn with_include_threads(mut self, include_threads: bool) -> Self {
+        self.include_threads = include_threads;
+        self
+    }
+
+    pub fn with_parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    async fn get_channel(&self, channel_id: u64) -> Result<Channel, ScraperError> {
+        self.discord_api_client
+            .get_channel(channel_id)
+            .await
+            .map_err(ScraperError::DiscordApiError)
+    }
+
+    pub async fn list_guild_channels(
+        &self,
+        guild_id: u64,
+        channel_filter: &ChannelFilter,
+    ) -> Result<Vec<Channel>, ScraperError> {
+        let guild_channels = self
+            .discord_api_client
+            .get_guild_channels(guild_id)
+            .await
+            .map_err(ScraperError::DiscordApiError)?;
+        Ok(channel_filter.apply(guild_channels))
+    }
+
     async fn scrape_msgs_before_msg(
         &self,
         channel_id: u64,
         message_id: u64,
-        use_personal: bool,
-    ) -> Result<Vec<crate::discord_api::Message>, ScraperError> {
+    ) -> Result<MessagePage, ScraperError> {
         let possible_messages = if message_id == 0 {
-            self.discord_api_client.get_channel_msgs(channel_id, use_personal).await
+            self.discord_api_client.get_channel_msgs(channel_id).await
         } else {
             self.discord_api_client
-                .get_channel_msgs_before_msg(channel_id, message_id, use_personal)
+                .get_channel_msgs_before_msg(channel_id, message_id)
                 .await
         };
-        match possible_messages {
-            Err(error) => match error {
-                DiscordApiError::UnexpectedResponseStatusCode(status_code, response) => {
-                    if status_code == 503 {
-                        tracing::warn!(
-                            "Received HTTP 503 from the Discord API, waiting {} seconds before retrying. See: {:#?}",
-                            SECONDS_TO_WAIT_IN_CASE_OF_HTTP_503,
-                            response
-                        );
-                        time::sleep(time::Duration::from_secs(SECONDS_TO_WAIT_IN_CASE_OF_HTTP_503 as u64))
-                            .await;
-                        return self.scrape_msgs_before_msg(channel_id, message_id, use_personal).await;
+        possible_messages.map_err(ScraperError::DiscordApiError)
+    }
+
+    pub async fn scrape_channel(
+        &self,
+        channel_id: u64,
+        save_target: &SaveTarget,
+    ) -> Result<ChannelScrapeSummary, ScraperError> {
+        let save_target = save_target.connect().await?;
+        self.scrape_channel_into(channel_id, &save_target).await
+    }
+
+    // Scrapes up to `concurrency` channels at once, yielding the result of each channel as soon as
+    // it completes. All the channels share the rate limiter of the client and the connection to
+    // the save target.
+    pub async fn scrape_channels<'a>(
+        &'a self,
+        channel_ids: Vec<u64>,
+        save_target: &SaveTarget,
+        concurrency: usize,
+    ) -> Result<
+        impl Stream<Item = (u64, Result<ChannelScrapeSummary, ScraperError>)> + 'a,
+        ScraperError,
+    > {
+        let save_target = Arc::new(save_target.connect().await?);
+        Ok(stream::iter(channel_ids)
+            .map(move |channel_id| {
+                let save_target = Arc::clone(&save_target);
+                async move {
+                    let result = self.scrape_channel_into(channel_id, &save_target).await;
+                    (channel_id, result)
+                }
+            })
+            .buffer_unordered(concurrency.max(1)))
+    }
+
+    // Lazily pages through the history of a channel, newest message first, within the bounds of
+    // the scraper. Nothing is saved: rejected messages are skipped in lenient mode and end the
+    // stream with an error in strict mode.
+    pub fn messages(
+        &self,
+        channel_id: u64,
+    ) -> impl Stream<Item = Result<Message, ScraperError>> + '_ {
+        // 0 starts from the newest message of the channel, `None` ends the stream.
+        let start_message_id = Some(self.bounds.before_message_id.unwrap_or(0));
+        stream::unfold(start_message_id, move |last_message_id| async move {
+            let mut message_page = match self
+                .scrape_msgs_before_msg(channel_id, last_message_id?)
+                .await
+            {
+                Ok(message_page) => message_page,
+                Err(error) => return Some((Err(error), None)),
+            };
+            if message_page.is_empty() {
+                return None;
+            }
+            if self.parse_mode == ParseMode::Strict && !message_page.rejected.is_empty() {
+                let error = ScraperError::RejectedMessages(
+                    message_page.rejected.len(),
+                    channel_id,
+                    message_page.rejected[0].reason.clone(),
+                );
+                return Some((Err(error), None));
+            }
+            let reached_lower_bound = message_page
+                .oldest_message_id
+                .is_some_and(|oldest_message_id| {
+                    !self.bounds.is_after_lower_bound(oldest_message_id)
+                });
+            let next_message_id = if reached_lower_bound {
+                message_page.retain(|message_id| self.bounds.is_after_lower_bound(message_id));
+                None
+            } else {
+                message_page.oldest_message_id
+            };
+            Some((Ok(message_page.messages), next_message_id))
+        })
+        .flat_map(|messages| {
+            let messages = match messages {
+                Ok(messages) => messages.into_iter().map(Ok).collect(),
+                Err(error) => vec![Err(error)],
+            };
+            stream::iter(messages)
+        })
+    }
+
+    // Scrapes a channel into a saver of the caller's own, e.g. one that keeps messages in memory.
+    pub async fn scrape_channel_with_saver(
+        &self,
+        channel_id: u64,
+        saver: &mut (dyn MessageSaver + Send + Sync),
+    ) -> Result<ChannelScrapeSummary, ScraperError> {
+        let channel = self.get_channel(channel_id).await?;
+        self.scrape_named_channel(&channel, saver).await
+    }
+
+    async fn scrape_channel_into(
+        &self,
+        channel_id: u64,
+        save_target: &ConnectedSaveTarget,
+    ) -> Result<ChannelScrapeSummary, ScraperError> {
+        let channel = self.get_channel(channel_id).await?;
+        let mut saver = save_target.saver(channel_id, &channel_name(&channel)).await?;
+        let mut summary = self.scrape_named_channel(&channel, saver.as_mut()).await?;
+        summary.output_path = save_target.output_path(channel_id, &summary.channel_name);
+        Ok(summary)
+    }
+
+    async fn scrape_named_channel(
+        &self,
+        channel: &Channel,
+        saver: &mut (dyn MessageSaver + Send + Sync),
+    ) -> Result<ChannelScrapeSummary, ScraperError> {
+        let start_instant = Instant::now();
+        let mut summary = ChannelScrapeSummary::new(channel.id, channel_name(channel));
+        saver.start_scrape_run(channel.id).await?;
+        let result = self
+            .scrape_channel_and_threads(channel, saver, &mut summary)
+            .await;
+        let run_finished = saver
+            .finish_scrape_run(&ScrapeRunOutcome {
+                saved_messages: summary.saved_messages,
+                rejected_messages: summary.rejected_messages,
+                completed: result.is_ok(),
+            })
+            .await;
+        // Whatever was saved before a failure stays readable.
+        let finished = saver.finish().await;
+        result?;
+        run_finished?;
+        finished?;
+        summary.duration = start_instant.elapsed();
+        Ok(summary)
+    }
+
+    async fn scrape_channel_and_threads(
+        &self,
+        channel: &Channel,
+        saver: &mut (dyn MessageSaver + Send + Sync),
+        summary: &mut ChannelScrapeSummary,
+    ) -> Result<(), ScraperError> {
+        let channel_id = channel.id;
+        let channel_name = channel_name(channel);
+        saver.save_channels(slice::from_ref(channel)).await?;
+        // Forum posts are all threads, the forum itself has no messages.
+        if !(self.include_threads && channel.is_forum()) {
+            self.scrape_msgs_into(channel_id, None, &channel_name, saver, summary)
+                .await?;
+        }
+        if self.include_threads && channel.has_threads() {
+            let threads = self.list_channel_threads(channel).await?;
+            saver.save_channels(&threads).await?;
+            for thread in threads {
+                let thread_name = thread.name.clone().unwrap_or_default();
+                tracing::info!("Scraping thread `{}` of `{}`.", thread_name, channel_name);
+                match self
+                    .scrape_msgs_into(
+                        thread.id,
+                        Some(channel_id),
+                        &thread_name,
+                        saver,
+                        summary,
+                    )
+                    .await
+                {
+                    Ok(()) => summary.scraped_threads += 1,
+                    Err(error) if error.is_missing_access() => {
+                        tracing::warn!("Skipping thread `{}`, the bot can't read it.", thread_name)
                     }
-                    Err(ScraperError::DiscordApiError(
-                        DiscordApiError::UnexpectedResponseStatusCode(status_code, response),
-                    ))
+                    Err(error) => return Err(error),
                 }
-                _ => Err(ScraperError::DiscordApiError(error)),
-            },
-            Ok(messages) => Ok(messages),
+            }
         }
+        Ok(())
     }
 
-    pub async fn scrape_channel(
+    // Scrapes a channel or thread into the given saver, `parent_channel_id` being set for threads.
+    async fn scrape_msgs_into(
         &self,
         channel_id: u64,
-        save_target: &SaveTarget,
-    ) -> Result<(Option<PathBuf>, u64), ScraperError> {
-        let (channel_last_msg_id, channel_name) = self.get_channel_info(channel_id).await?;
-        let start_timestamp = chrono::Local::now().timestamp();
-        let mut saver: Box<dyn MessageSaver + Send + Sync> = match save_target {
-            SaveTarget::Jsonl => {
-                std::fs::create_dir_all("storage").unwrap();
-                let output_file_name = format!("storage/{}.jsonl", channel_name);
-                Box::new(JsonlSaver::new(&output_file_name).await?)
-            }
-            SaveTarget::Sql(database_url) => Box::new(SqlSaver::new(database_url).await?),
+        parent_channel_id: Option<u64>,
+        channel_name: &str,
+        saver: &mut (dyn MessageSaver + Send + Sync),
+        summary: &mut ChannelScrapeSummary,
+    ) -> Result<(), ScraperError> {
+        if self.incremental {
+            if let Some(newest_archived_message_id) =
+                saver.newest_archived_message_id(channel_id).await?
+            {
+                return self
+                    .sync_new_msgs(
+                        channel_id,
+                        parent_channel_id,
+                        newest_archived_message_id,
+                        saver,
+                        summary,
+                    )
+                    .await;
+            }
+            tracing::info!(
+                "Nothing archived yet for channel `{}`, scraping its whole history.",
+                channel_name
+            );
+        }
+        let previous_checkpoint = if self.resume {
+            saver.load_checkpoint(channel_id).await?
+        } else {
+            None
         };
-        let mut last_message_id = channel_last_msg_id;
-        let format_request = !channel_name.starts_with("dm_");
+        let mut checkpoint = match previous_checkpoint {
+            Some(checkpoint) if checkpoint.completed => {
+                tracing::info!(
+                    "Channel `{}` was already scraped completely, nothing to resume.",
+                    channel_name
+                );
+                return Ok(());
+            }
+            Some(checkpoint) => {
+                tracing::info!(
+                    "Resuming channel `{}` from before message {:?}.",
+                    channel_name,
+                    checkpoint.oldest_message_id
+                );
+                checkpoint
+            }
+            None => ScrapeCheckpoint::new(channel_id),
+        };
+        // 0 starts from the newest message of the channel.
+        let mut last_message_id = checkpoint
+            .oldest_message_id
+            .or(self.bounds.before_message_id)
+            .unwrap_or(0);
+        // Only a scrape that reached the first message of the channel without losing a page on the
+        // way is complete, a bounded one leaves the older history for a later resume.
+        let mut reached_first_message = false;
+        let mut had_save_failure = false;
         loop {
-            let messages = self
-                .scrape_msgs_before_msg(channel_id, last_message_id, format_request)
+            let mut message_page = self
+                .scrape_msgs_before_msg(channel_id, last_message_id)
                 .await?;
-            if let Some(last_message) = messages.last() {
-                last_message_id = last_message.message_id;
-            } else {
+            if message_page.is_empty() {
                 tracing::info!("No more messages to scrape.");
+                reached_first_message = true;
+                break;
+            }
+            let reached_lower_bound = message_page
+                .oldest_message_id
+                .is_some_and(|oldest_message_id| {
+                    !self.bounds.is_after_lower_bound(oldest_message_id)
+                });
+            if reached_lower_bound {
+                message_page.retain(|message_id| self.bounds.is_after_lower_bound(message_id));
+            }
+            message_page.set_parent_channel_id(parent_channel_id);
+            if !self.save_page(saver, &message_page, summary).await? {
+                had_save_failure = true;
+            } else if !had_save_failure {
+                // Past a page that failed to save, the checkpoint stays before it so that a resume
+                // fetches it again.
+                checkpoint.record_older_page(&message_page);
+                if reached_lower_bound {
+                    // The messages past the bound were dropped from the page, a resume starts
+                    // right at the bound instead.
+                    checkpoint.oldest_message_id = self
+                        .bounds
+                        .after_message_id
+                        .map(|after_message_id| after_message_id + 1);
+                }
+                if let Err(error) = saver.save_checkpoint(&checkpoint).await {
+                    tracing::error!("Failed to save the scrape checkpoint: {:#?}", error);
+                }
+            }
+            if reached_lower_bound {
+                tracing::info!("Reached the lower bound of the scrape.");
                 break;
             }
-            if let Err(error) = saver.save_messages(&messages).await {
-                tracing::error!("Failed to save message batch: {:#?}", error);
+            match message_page.oldest_message_id {
+                Some(oldest_message_id) => last_message_id = oldest_message_id,
+                None => {
+                    tracing::warn!("Couldn't find a message id to continue paging from.");
+                    break;
+                }
             }
         }
-        let time_it_took_in_secs =
-            ((chrono::Local::now().timestamp() - start_timestamp) / 60) as u64;
-        let output_path = if let SaveTarget::Jsonl = save_target {
-            Some(PathBuf::from(format!("storage/{}.jsonl", channel_name)))
-        } else {
-            None
-        };
-        Ok((output_path, time_it_took_in_secs))
+        if had_save_failure {
+            tracing::warn!(
+                "Some messages of `{}` failed to save, resume the scrape to fetch them again.",
+                channel_name
+            );
+        } else if reached_first_message {
+            checkpoint.completed = true;
+            saver.save_checkpoint(&checkpoint).await?;
+        }
+        Ok(())
+    }
+
+    // Active threads are listed per guild, archived ones per channel. Private archived threads
+    // need the Manage Threads permission so they are skipped when it's missing.
+    async fn list_channel_threads(&self, channel: &Channel) -> Result<Vec<Channel>, ScraperError> {
+        let mut threads = Vec::new();
+        if let Some(guild_id) = channel.guild_id {
+            threads.extend(
+                self.active_guild_threads(guild_id)
+                    .await?
+                    .iter()
+                    .filter(|thread| thread.parent_id == Some(channel.id))
+                    .cloned(),
+            );
+        }
+        for private in [false, true] {
+            match self
+                .discord_api_client
+                .get_archived_threads(channel.id, private)
+                .await
+                .map_err(ScraperError::DiscordApiError)
+            {
+                Ok(archived_threads) => threads.extend(archived_threads),
+                Err(error) if private && error.is_missing_access() => tracing::info!(
+                    "Can't list the private archived threads of `{}`, skipping them.",
+                    channel.name.as_deref().unwrap_or_default()
+                ),
+                Err(error) => return Err(error),
+            }
+        }
+        let mut seen_thread_ids = HashSet::new();
+        threads.retain(|thread| seen_thread_ids.insert(thread.id));
+        Ok(threads)
+    }
+
+    // The lock is held while fetching, so that channels scraped concurrently wait for the first
+    // one to fetch the threads of their guild rather than fetching them again.
+    async fn active_guild_threads(&self, guild_id: u64) -> Result<Arc<Vec<Channel>>, ScraperError> {
+        let mut active_guild_threads = self.active_guild_threads.lock().await;
+        if let Some(active_threads) = active_guild_threads.get(&guild_id) {
+            return Ok(Arc::clone(active_threads));
+        }
+        let active_threads = Arc::new(
+            self.discord_api_client
+                .get_active_guild_threads(guild_id)
+                .await
+                .map_err(ScraperError::DiscordApiError)?,
+        );
+        active_guild_threads.insert(guild_id, Arc::clone(&active_threads));
+        Ok(active_threads)
+    }
+
+    // Pages forward from the newest archived message until caught up with the channel.
+    async fn sync_new_msgs(
+        &self,
+        channel_id: u64,
+        parent_channel_id: Option<u64>,
+        newest_archived_message_id: u64,
+        saver: &mut (dyn MessageSaver + Send + Sync),
+        summary: &mut ChannelScrapeSummary,
+    ) -> Result<(), ScraperError> {
+        let mut checkpoint = saver
+            .load_checkpoint(channel_id)
+            .await?
+            .unwrap_or_else(|| ScrapeCheckpoint::new(channel_id));
+        let mut after_message_id =
+            newest_archived_message_id.max(self.bounds.after_message_id.unwrap_or(0));
+        loop {
+            let mut message_page = self
+                .discord_api_client
+                .get_channel_msgs_after_msg(channel_id, after_message_id)
+                .await
+                .map_err(ScraperError::DiscordApiError)?;
+            if message_page.is_empty() {
+                tracing::info!("Caught up with the channel, no more new messages.");
+                break;
+            }
+            let reached_upper_bound = message_page
+                .newest_message_id
+                .is_some_and(|newest_message_id| {
+                    !self.bounds.is_before_upper_bound(newest_message_id)
+                });
+            if reached_upper_bound {
+                message_page.retain(|message_id| self.bounds.is_before_upper_bound(message_id));
+            }
+            message_page.set_parent_channel_id(parent_channel_id);
+            if self.save_page(saver, &message_page, summary).await? {
+                checkpoint.record_newer_page(&message_page);
+                if let Err(error) = saver.save_checkpoint(&checkpoint).await {
+                    tracing::error!("Failed to save the scrape checkpoint: {:#?}", error);
+                }
+            }
+            if reached_upper_bound {
+                tracing::info!("Reached the upper bound of the scrape.");
+                break;
+            }
+            match message_page.newest_message_id {
+                Some(newest_message_id) => after_message_id = newest_message_id,
+                None => {
+                    tracing::warn!("Couldn't find a message id to continue paging from.");
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Quarantines the rejected part of the page and saves the rest, returns whether saving
+    // succeeded.
+    async fn save_page(
+        &self,
+        saver: &mut (dyn MessageSaver + Send + Sync),
+        message_page: &MessagePage,
+        summary: &mut ChannelScrapeSummary,
+    ) -> Result<bool, ScraperError> {
+        if !message_page.rejected.is_empty() {
+            summary.record_rejected(&message_page.rejected);
+            if let Err(error) = saver.save_rejected_messages(&message_page.rejected).await {
+                tracing::error!("Failed to quarantine rejected messages: {:#?}", error);
+            }
+            if self.parse_mode == ParseMode::Strict {
+                return Err(ScraperError::RejectedMessages(
+                    message_page.rejected.len(),
+                    summary.channel_id,
+                    message_page.rejected[0].reason.clone(),
+                ));
+            }
+        }
+        if let Err(error) = saver.save_messages(&message_page.messages).await {
+            tracing::error!("Failed to save message batch: {:#?}", error);
+            return Ok(false);
+        }
+        summary.saved_messages += message_page.messages.len() as u64;
+        Ok(true)
     }
 }
 
-pub async fn convert_jsonl_file_into_json(path: &Path) -> Result<PathBuf, FileConversionError> {
+// Converts a JSONL archive into a JSON array next to it, e.g. `general.jsonl` or
+// `general.jsonl.gz` into `general.json`.
+pub async fn convert_jsonl_file_into_json(
+    path: &Path,
+    parse_mode: ParseMode,
+) -> Result<PathBuf, FileConversionError> {
     let jsonl_file_path_buf = path.to_path_buf();
-    let jsonl_file = File::open(path).await.map_err(|error| {
+    let jsonl_file = compression::open_reader(path).await.map_err(|error| {
         FileConversionError::ReadFileContents(jsonl_file_path_buf.clone(), error)
     })?;
-    if let Some(jsonl_file_stem) = path.file_stem() {
+    let uncompressed_path = compression::uncompressed_path(path);
+    if let Some(jsonl_file_stem) = uncompressed_path.file_stem() {
         if let Some(dir_path) = jsonl_file_path_buf.parent() {
             let jsonl_file_stem_string = jsonl_file_stem.to_string_lossy();
             let json_file_name = format!("{}.json", jsonl_file_stem_string);
             let mut json_file_path = dir_path.to_path_buf();
             json_file_path.push(json_file_name);
-            let mut jsonl_lines = BufReader::new(jsonl_file).lines();
+            let mut jsonl_lines = jsonl_file.lines();
             let mut json_value_data: Vec<Value> = Vec::new();
-            while let Ok(Some(line)) = jsonl_lines.next_line().await {
-                if let Ok(value) = serde_json::from_str::<Value>(&line) {
-                    json_value_data.push(value);
+            let mut line_number = 0;
+            let mut skipped_lines = 0;
+            while let Some(line) = jsonl_lines.next_line().await.map_err(|error| {
+                FileConversionError::ReadFileContents(jsonl_file_path_buf.clone(), error)
+            })? {
+                line_number += 1;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<Value>(&line) {
+                    Ok(value) => json_value_data.push(value),
+                    Err(error) if parse_mode == ParseMode::Strict => {
+                        return Err(FileConversionError::InvalidLine(
+                            jsonl_file_path_buf,
+                            line_number,
+                            error,
+                        ));
+                    }
+                    Err(error) => {
+                        tracing::warn!("Skipping line {} of the input: {}", line_number, error);
+                        skipped_lines += 1;
+                    }
                 }
             }
+            if skipped_lines > 0 {
+                tracing::warn!(
+                    "Skipped {} of {} lines that weren't valid JSON.",
+                    skipped_lines,
+                    line_number
+                );
+            }
             let json_string = serde_json::to_string_pretty(&json_value_data)
                 .map_err(FileConversionError::SerializeJsonlItems)?;
             let mut json_file = File::create(json_file_path.clone()).await
                 .map_err(|error| FileConversionError::CreateOutputFile(json_file_path.clone(), error))?;
             json_file.write_all(json_string.as_bytes()).await
                 .map_err(|error| FileConversionError::WriteIntoFile(json_file_path.clone(), error))?;
+            // Tokio hands writes over to a background thread, flushing waits for them to land.
+            json_file.flush().await
+                .map_err(|error| FileConversionError::WriteIntoFile(json_file_path.clone(), error))?;
             Ok(json_file_path)
         } else {
             Err(FileConversionError::InvalidPath(
@@ -187,4 +742,232 @@ pub async fn convert_jsonl_file_into_json(path: &Path) -> Result<PathBuf, FileCo
             InvalidPathError::NoFileStem(jsonl_file_path_buf),
         ))
     }
-}
\ No newline at end of file
+}
+
+// Converts a JSONL archive of messages into a Parquet file next to it, e.g. `general.jsonl` into
+// `general.parquet`.
+pub async fn convert_jsonl_file_into_parquet(
+    path: &Path,
+    parse_mode: ParseMode,
+    row_group_size: usize,
+) -> Result<PathBuf, FileConversionError> {
+    let parquet_file_path = converted_file_path(path, "parquet")?;
+    let mut parquet_saver = ParquetSaver::new(&parquet_file_path, row_group_size)
+        .await
+        .map_err(|error| FileConversionError::SaveMessages(parquet_file_path.clone(), error))?;
+    convert_jsonl_file_with_saver(path, parse_mode, &parquet_file_path, &mut parquet_saver).await?;
+    Ok(parquet_file_path)
+}
+
+// Converts a JSONL archive of messages into a CSV or TSV file next to it, e.g. `general.jsonl`
+// into `general.csv`.
+pub async fn convert_jsonl_file_into_csv(
+    path: &Path,
+    parse_mode: ParseMode,
+    csv_options: &CsvOptions,
+) -> Result<PathBuf, FileConversionError> {
+    let csv_file_path = converted_file_path(path, csv_options.extension())?;
+    let mut csv_saver = CsvSaver::new(&csv_file_path, csv_options.clone())
+        .await
+        .map_err(|error| FileConversionError::SaveMessages(csv_file_path.clone(), error))?;
+    convert_jsonl_file_with_saver(path, parse_mode, &csv_file_path, &mut csv_saver).await?;
+    Ok(csv_file_path)
+}
+
+// Reads a JSONL archive into a transcript titled after the archive, which is named after its
+// channel, e.g. `general` for `general-659069446438125570.jsonl`.
+pub async fn read_jsonl_transcript(
+    path: &Path,
+    parse_mode: ParseMode,
+) -> Result<Transcript, FileConversionError> {
+    let uncompressed_path = compression::uncompressed_path(path);
+    let Some(channel_name) = uncompressed_path.file_stem() else {
+        return Err(FileConversionError::InvalidPath(
+            InvalidPathError::NoFileStem(path.to_path_buf()),
+        ));
+    };
+    let channel_name = channel_name.to_string_lossy().into_owned();
+    let mut collector = MessageCollector::default();
+    convert_jsonl_file_with_saver(path, parse_mode, path, &mut collector).await?;
+    let channel_id = collector
+        .messages
+        .first()
+        .map(|message| message.parent_channel_id.unwrap_or(message.channel_id));
+    let channel_name = match channel_id {
+        Some(channel_id) => channel_name
+            .strip_suffix(&format!("-{}", channel_id))
+            .map(str::to_string)
+            .unwrap_or(channel_name),
+        None => channel_name,
+    };
+    let transcript = Transcript::new(channel_name.clone(), collector.messages);
+    Ok(match channel_id {
+        Some(channel_id) => transcript.with_channel_name(channel_id, channel_name),
+        None => transcript,
+    })
+}
+
+// Writes the pages of an HTML transcript, the first one at `path`, and returns their paths.
+pub async fn write_html_transcript(
+    transcript: &Transcript,
+    path: &Path,
+    messages_per_page: usize,
+) -> Result<Vec<PathBuf>, FileConversionError> {
+    let mut page_paths = Vec::new();
+    for (page_path, page) in html::render_pages(transcript, path, messages_per_page) {
+        tokio::fs::write(&page_path, page)
+            .await
+            .map_err(|error| FileConversionError::WriteIntoFile(page_path.clone(), error))?;
+        page_paths.push(page_path);
+    }
+    Ok(page_paths)
+}
+
+// Writes a transcript in the given format, HTML ones being split into pages, and returns the
+// paths of the files written.
+pub async fn write_transcript(
+    transcript: &Transcript,
+    path: &Path,
+    format: TranscriptFormat,
+    messages_per_page: usize,
+) -> Result<Vec<PathBuf>, FileConversionError> {
+    let contents = match format {
+        TranscriptFormat::Html => {
+            return write_html_transcript(transcript, path, messages_per_page).await
+        }
+        TranscriptFormat::Markdown => text::render_markdown(transcript),
+        TranscriptFormat::Text => text::render_plain_text(transcript),
+    };
+    tokio::fs::write(path, contents)
+        .await
+        .map_err(|error| FileConversionError::WriteIntoFile(path.to_path_buf(), error))?;
+    Ok(vec![path.to_path_buf()])
+}
+
+// Renders a JSONL archive of messages into a transcript next to it, e.g. `general.jsonl` into
+// `general.md`, or into `general.html`, `general.page-2.html` and so on.
+pub async fn convert_jsonl_file_into_transcript(
+    path: &Path,
+    parse_mode: ParseMode,
+    format: TranscriptFormat,
+    messages_per_page: usize,
+) -> Result<Vec<PathBuf>, FileConversionError> {
+    let transcript_file_path = converted_file_path(path, format.extension())?;
+    let transcript = read_jsonl_transcript(path, parse_mode).await?;
+    write_transcript(&transcript, &transcript_file_path, format, messages_per_page).await
+}
+
+pub async fn convert_jsonl_file_into_html(
+    path: &Path,
+    parse_mode: ParseMode,
+    messages_per_page: usize,
+) -> Result<Vec<PathBuf>, FileConversionError> {
+    convert_jsonl_file_into_transcript(path, parse_mode, TranscriptFormat::Html, messages_per_page)
+        .await
+}
+
+// DM channels have no name, they're named after their id instead.
+fn channel_name(channel: &Channel) -> String {
+    channel
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("dm_{}", channel.id))
+}
+
+// `general.jsonl` or `general.jsonl.zst` + `csv` -> `general.csv`
+fn converted_file_path(path: &Path, extension: &str) -> Result<PathBuf, FileConversionError> {
+    let uncompressed_path = compression::uncompressed_path(path);
+    if uncompressed_path.file_stem().is_none() {
+        return Err(FileConversionError::InvalidPath(
+            InvalidPathError::NoFileStem(path.to_path_buf()),
+        ));
+    }
+    Ok(uncompressed_path.with_extension(extension))
+}
+
+// Feeds the messages of a JSONL archive into a saver writing `output_path`, invalid lines being
+// skipped in lenient mode.
+async fn convert_jsonl_file_with_saver(
+    path: &Path,
+    parse_mode: ParseMode,
+    output_path: &Path,
+    saver: &mut (dyn MessageSaver + Send + Sync),
+) -> Result<(), FileConversionError> {
+    let jsonl_file_path_buf = path.to_path_buf();
+    let jsonl_file = compression::open_reader(path).await.map_err(|error| {
+        FileConversionError::ReadFileContents(jsonl_file_path_buf.clone(), error)
+    })?;
+    let save_error = |error| FileConversionError::SaveMessages(output_path.to_path_buf(), error);
+    let mut jsonl_lines = jsonl_file.lines();
+    let mut line_number = 0;
+    let mut skipped_lines = 0;
+    while let Some(line) = jsonl_lines.next_line().await.map_err(|error| {
+        FileConversionError::ReadFileContents(jsonl_file_path_buf.clone(), error)
+    })? {
+        line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Message>(&line) {
+            Ok(message) => saver.save_messages(&[message]).await.map_err(save_error)?,
+            Err(error) if parse_mode == ParseMode::Strict => {
+                return Err(FileConversionError::InvalidLine(
+                    jsonl_file_path_buf,
+                    line_number,
+                    error,
+                ));
+            }
+            Err(error) => {
+                tracing::warn!("Skipping line {} of the input: {}", line_number, error);
+                skipped_lines += 1;
+            }
+        }
+    }
+    if skipped_lines > 0 {
+        tracing::warn!(
+            "Skipped {} of {} lines that weren't valid messages.",
+            skipped_lines,
+            line_number
+        );
+    }
+    saver.finish().await.map_err(save_error)
+}
+
+// Keeps the messages of an archive in memory, for the conversions that need all of them at once.
+#[derive(Default)]
+struct MessageCollector {
+    messages: Vec<Message>,
+}
+
+#[async_trait]
+impl MessageSaver for MessageCollector {
+    async fn save_messages(&mut self, messages: &[Message]) -> eyre::Result<()> {
+        self.messages.extend_from_slice(messages);
+        Ok(())
+    }
+
+    async fn save_rejected_messages(
+        &mut self,
+        _rejected_messages: &[RejectedMessage],
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    async fn load_checkpoint(
+        &mut self,
+        _channel_id: u64,
+    ) -> eyre::Result<Option<ScrapeCheckpoint>> {
+        Ok(None)
+    }
+
+    async fn newest_archived_message_id(
+        &mut self,
+        _channel_id: u64,
+    ) -> eyre::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    async fn save_checkpoint(&mut self, _checkpoint: &ScrapeCheckpoint) -> eyre::Result<()> {
+        Ok(())
+    }
+}
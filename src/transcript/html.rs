@@ -0,0 +1,437 @@
+use super::markdown::{escape_html, render_markdown};
+use super::{
+    emoji_text, format_size, group_messages, message_timestamp, system_message_text, MessageGroup,
+    Transcript,
+};
+use crate::discord_api::models::Embed;
+use crate::discord_api::Message;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+// Self-contained HTML pages, the styles are inlined and nothing is loaded from Discord: avatars,
+// images and attachments are links, so a transcript opens the same offline and years later.
+
+pub const DEFAULT_MESSAGES_PER_PAGE: usize = 1000;
+
+const STYLE: &str = r#"
+body { margin: 0; background: #313338; color: #dbdee1; font: 16px/1.375 "gg sans", "Helvetica Neue", Helvetica, Arial, sans-serif; }
+a { color: #00a8fc; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.preamble { padding: 16px; border-bottom: 1px solid #3f4147; }
+.preamble h1 { margin: 0 0 4px; font-size: 20px; color: #f2f3f5; }
+.preamble p { margin: 0; color: #949ba4; font-size: 14px; }
+nav { padding: 8px 16px; font-size: 14px; color: #949ba4; }
+nav a, nav span { margin-right: 12px; }
+.day { display: flex; align-items: center; margin: 16px; color: #949ba4; font-size: 12px; font-weight: 600; }
+.day::before, .day::after { content: ""; flex: 1; border-top: 1px solid #3f4147; }
+.day span { padding: 0 8px; }
+.group { padding: 2px 16px 2px 72px; margin-top: 16px; position: relative; }
+.group .reply { margin-left: -56px; padding-left: 56px; font-size: 14px; color: #949ba4; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+.group .reply .reply-author { color: #f2f3f5; font-weight: 500; }
+.group-header { margin-left: -56px; }
+.author { color: #f2f3f5; font-weight: 500; }
+.bot-tag { margin-left: 4px; padding: 0 4px; border-radius: 3px; background: #5865f2; color: #fff; font-size: 10px; font-weight: 600; vertical-align: 2px; }
+.group-header time, .thread { margin-left: 6px; color: #949ba4; font-size: 12px; }
+.message { position: relative; padding: 1px 0; word-wrap: break-word; }
+.message .message-time { position: absolute; left: -56px; width: 48px; text-align: right; color: #949ba4; font-size: 11px; visibility: hidden; }
+.message:hover .message-time { visibility: visible; }
+.message:target { background: rgba(250, 168, 26, 0.1); }
+.edited { margin-left: 4px; color: #949ba4; font-size: 10px; }
+.system { color: #949ba4; font-style: italic; }
+.mention { padding: 0 2px; border-radius: 3px; background: rgba(88, 101, 242, 0.3); color: #c9cdfb; font-weight: 500; }
+.emoji { color: #f2f3f5; }
+.timestamp { padding: 0 2px; border-radius: 3px; background: rgba(255, 255, 255, 0.06); }
+.spoiler { border-radius: 3px; background: #1e1f22; color: transparent; }
+.spoiler:hover { color: inherit; }
+.subtext { color: #949ba4; font-size: 12px; }
+.heading { margin: 8px 0 0; color: #f2f3f5; }
+code { padding: 0 2px; border-radius: 3px; background: #2b2d31; font-family: Consolas, "Courier New", monospace; font-size: 85%; }
+pre { margin: 4px 0; padding: 8px; border: 1px solid #1e1f22; border-radius: 4px; background: #2b2d31; white-space: pre-wrap; }
+pre code { padding: 0; background: none; }
+blockquote { margin: 0; padding: 0 8px 0 12px; border-left: 4px solid #4e5058; }
+.attachments, .reactions { margin: 4px 0; padding: 0; list-style: none; }
+.attachments li { margin: 2px 0; padding: 6px 10px; border: 1px solid #2b2d31; border-radius: 4px; background: #2b2d31; display: table; }
+.attachments .size { margin-left: 6px; color: #949ba4; font-size: 12px; }
+.reactions li { display: inline-block; margin-right: 4px; padding: 0 6px; border-radius: 8px; background: #2b2d31; font-size: 14px; }
+.stickers { color: #949ba4; font-size: 14px; }
+.embed { max-width: 520px; margin: 4px 0; padding: 8px 16px 12px 12px; border-left: 4px solid #1e1f22; border-radius: 4px; background: #2b2d31; font-size: 14px; }
+.embed-provider, .embed-footer, .embed-media { color: #949ba4; font-size: 12px; }
+.embed-author { color: #f2f3f5; font-weight: 600; }
+.embed-title { color: #f2f3f5; font-weight: 600; }
+.embed-fields { display: flex; flex-wrap: wrap; gap: 8px; margin-top: 8px; }
+.embed-field { flex: 1 1 100%; }
+.embed-field.inline { flex: 1 1 30%; }
+.embed-field-name { color: #f2f3f5; font-weight: 600; }
+footer { padding: 16px; color: #949ba4; font-size: 12px; }
+"#;
+
+// `general.html` holds the first page, `general.page-2.html` the second and so on.
+pub fn page_path(path: &Path, page_number: usize) -> PathBuf {
+    if page_number <= 1 {
+        return path.to_path_buf();
+    }
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "html".to_string());
+    path.with_extension(format!("page-{}.{}", page_number, extension))
+}
+
+fn page_file_name(path: &Path, page_number: usize) -> String {
+    page_path(path, page_number)
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn format_datetime(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M").to_string()
+}
+
+// Renders the transcript into pages of `messages_per_page` messages, to be written at the
+// `page_path`s of `path`. An empty transcript still gets a page.
+pub fn render_pages(
+    transcript: &Transcript,
+    path: &Path,
+    messages_per_page: usize,
+) -> Vec<(PathBuf, String)> {
+    let messages_per_page = messages_per_page.max(1);
+    let pages: Vec<&[Message]> = if transcript.messages().is_empty() {
+        vec![&[]]
+    } else {
+        transcript.messages().chunks(messages_per_page).collect()
+    };
+    let renderer = PageRenderer {
+        transcript,
+        path,
+        messages_per_page,
+        page_count: pages.len(),
+    };
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(index, messages)| {
+            let page_number = index + 1;
+            (
+                page_path(path, page_number),
+                renderer.render_page(page_number, messages),
+            )
+        })
+        .collect()
+}
+
+struct PageRenderer<'a> {
+    transcript: &'a Transcript,
+    path: &'a Path,
+    messages_per_page: usize,
+    page_count: usize,
+}
+
+impl PageRenderer<'_> {
+    fn render_page(&self, page_number: usize, messages: &[Message]) -> String {
+        let title = escape_html(self.transcript.title());
+        let mut html = String::new();
+        html.push_str(&format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+            title, STYLE
+        ));
+        html.push_str(&format!(
+            "<header class=\"preamble\">\n<h1>{}</h1>\n",
+            title
+        ));
+        let all_messages = self.transcript.messages();
+        if let (Some(first_message), Some(last_message)) =
+            (all_messages.first(), all_messages.last())
+        {
+            html.push_str(&format!(
+                "<p>{} messages from {} to {} UTC</p>\n",
+                all_messages.len(),
+                format_datetime(message_timestamp(first_message)),
+                format_datetime(message_timestamp(last_message))
+            ));
+        } else {
+            html.push_str("<p>No messages</p>\n");
+        }
+        html.push_str("</header>\n");
+        let navigation = self.render_navigation(page_number);
+        html.push_str(&navigation);
+        html.push_str("<main>\n");
+        let mut previous_day = None;
+        for group in group_messages(messages) {
+            let day = group.day();
+            if previous_day != Some(day) {
+                html.push_str(&format!(
+                    "<div class=\"day\"><span>{}</span></div>\n",
+                    day.format("%A, %-d %B %Y")
+                ));
+                previous_day = Some(day);
+            }
+            self.render_group(&mut html, &group);
+        }
+        html.push_str("</main>\n");
+        html.push_str(&navigation);
+        html.push_str(&format!(
+            "<footer>Exported {} messages, page {} of {}.</footer>\n</body>\n</html>\n",
+            all_messages.len(),
+            page_number,
+            self.page_count
+        ));
+        html
+    }
+
+    fn render_navigation(&self, page_number: usize) -> String {
+        if self.page_count <= 1 {
+            return String::new();
+        }
+        let mut html = String::from("<nav>");
+        if page_number > 1 {
+            html.push_str(&format!(
+                "<a href=\"{}\">&larr; Previous</a>",
+                escape_html(&page_file_name(self.path, page_number - 1))
+            ));
+        }
+        html.push_str(&format!(
+            "<span>Page {} of {}</span>",
+            page_number, self.page_count
+        ));
+        if page_number < self.page_count {
+            html.push_str(&format!(
+                "<a href=\"{}\">Next &rarr;</a>",
+                escape_html(&page_file_name(self.path, page_number + 1))
+            ));
+        }
+        html.push_str("</nav>\n");
+        html
+    }
+
+    // Link to a message, on whichever page it ended up.
+    fn message_link(&self, message_id: u64) -> Option<String> {
+        let index = self.transcript.message_index(message_id)?;
+        let page_number = index / self.messages_per_page + 1;
+        Some(format!(
+            "{}#message-{}",
+            escape_html(&page_file_name(self.path, page_number)),
+            message_id
+        ))
+    }
+
+    fn render_group(&self, html: &mut String, group: &MessageGroup) {
+        let first_message = group.first();
+        let names = self.transcript.names();
+        html.push_str("<article class=\"group\">\n");
+        if let Some(reply_to_message_id) = first_message.reply_to_message_id() {
+            self.render_reply(html, reply_to_message_id);
+        }
+        let timestamp = message_timestamp(first_message);
+        html.push_str(&format!(
+            "<div class=\"group-header\"><span class=\"author\" title=\"{}\">{}</span>",
+            first_message.author_id,
+            escape_html(&names.author(first_message))
+        ));
+        if first_message
+            .author
+            .as_ref()
+            .is_some_and(|author| author.bot)
+        {
+            html.push_str("<span class=\"bot-tag\">BOT</span>");
+        }
+        html.push_str(&format!(
+            "<time datetime=\"{}\">{}</time>",
+            timestamp.to_rfc3339(),
+            format_datetime(timestamp)
+        ));
+        if first_message.parent_channel_id.is_some() {
+            html.push_str(&format!(
+                "<span class=\"thread\">in thread #{}</span>",
+                escape_html(&names.channel(first_message.channel_id))
+            ));
+        }
+        html.push_str("</div>\n");
+        for message in &group.messages {
+            self.render_message(html, message);
+        }
+        html.push_str("</article>\n");
+    }
+
+    fn render_reply(&self, html: &mut String, reply_to_message_id: u64) {
+        let names = self.transcript.names();
+        html.push_str("<div class=\"reply\">&#8618; ");
+        match self.transcript.message(reply_to_message_id) {
+            Some(replied_message) => {
+                let snippet: String = replied_message.message.chars().take(100).collect();
+                let snippet = if snippet.is_empty() {
+                    "Click to see the message".to_string()
+                } else {
+                    snippet
+                };
+                html.push_str(&format!(
+                    "<span class=\"reply-author\">@{}</span> <a href=\"{}\">{}</a>",
+                    escape_html(&names.author(replied_message)),
+                    self.message_link(reply_to_message_id).unwrap_or_default(),
+                    escape_html(&snippet)
+                ));
+            }
+            None => {
+                html.push_str(&format!(
+                    "Replying to message {}, which isn't in this transcript",
+                    reply_to_message_id
+                ));
+            }
+        }
+        html.push_str("</div>\n");
+    }
+
+    fn render_message(&self, html: &mut String, message: &Message) {
+        let names = self.transcript.names();
+        let timestamp = message_timestamp(message);
+        html.push_str(&format!(
+            "<div class=\"message\" id=\"message-{}\"><time class=\"message-time\" datetime=\"{}\">{}</time>",
+            message.message_id,
+            timestamp.to_rfc3339(),
+            timestamp.format("%H:%M")
+        ));
+        match system_message_text(message.kind) {
+            Some(text) if message.message.is_empty() => {
+                html.push_str(&format!("<div class=\"content system\">{}</div>", text));
+            }
+            _ if message.message.is_empty() => {}
+            _ => {
+                html.push_str(&format!(
+                    "<div class=\"content\">{}",
+                    render_markdown(&message.message, names)
+                ));
+                if let Some(edited_timestamp) = message.edited_timestamp {
+                    html.push_str(&format!(
+                        "<span class=\"edited\" title=\"{}\">(edited)</span>",
+                        edited_timestamp.to_rfc3339()
+                    ));
+                }
+                html.push_str("</div>");
+            }
+        }
+        if !message.attachments.is_empty() {
+            html.push_str("<ul class=\"attachments\">");
+            for attachment in &message.attachments {
+                html.push_str(&format!(
+                    "<li><a href=\"{}\" rel=\"noreferrer\">{}</a><span class=\"size\">{}</span></li>",
+                    escape_html(&attachment.url),
+                    escape_html(&attachment.filename),
+                    format_size(attachment.size)
+                ));
+            }
+            html.push_str("</ul>");
+        }
+        for embed in &message.embeds {
+            self.render_embed(html, embed);
+        }
+        if !message.sticker_items.is_empty() {
+            let sticker_names: Vec<String> = message
+                .sticker_items
+                .iter()
+                .map(|sticker| escape_html(&sticker.name))
+                .collect();
+            html.push_str(&format!(
+                "<div class=\"stickers\">Sticker: {}</div>",
+                sticker_names.join(", ")
+            ));
+        }
+        if !message.reactions.is_empty() {
+            html.push_str("<ul class=\"reactions\">");
+            for reaction in &message.reactions {
+                html.push_str(&format!(
+                    "<li>{} {}</li>",
+                    escape_html(&emoji_text(&reaction.emoji)),
+                    reaction.count
+                ));
+            }
+            html.push_str("</ul>");
+        }
+        html.push_str("</div>\n");
+    }
+
+    fn render_embed(&self, html: &mut String, embed: &Embed) {
+        let names = self.transcript.names();
+        let border_color = embed
+            .color
+            .map(|color| format!(" style=\"border-color: #{:06x}\"", color))
+            .unwrap_or_default();
+        html.push_str(&format!("<div class=\"embed\"{}>", border_color));
+        let optional_link = |text: &str, url: Option<&String>| match url {
+            Some(url) => format!(
+                "<a href=\"{}\" rel=\"noreferrer\">{}</a>",
+                escape_html(url),
+                escape_html(text)
+            ),
+            None => escape_html(text),
+        };
+        if let Some(provider_name) = embed
+            .provider
+            .as_ref()
+            .and_then(|provider| provider.name.as_ref())
+        {
+            html.push_str(&format!(
+                "<div class=\"embed-provider\">{}</div>",
+                escape_html(provider_name)
+            ));
+        }
+        if let Some(author) = &embed.author {
+            html.push_str(&format!(
+                "<div class=\"embed-author\">{}</div>",
+                optional_link(&author.name, author.url.as_ref())
+            ));
+        }
+        if let Some(title) = &embed.title {
+            html.push_str(&format!(
+                "<div class=\"embed-title\">{}</div>",
+                optional_link(title, embed.url.as_ref())
+            ));
+        }
+        if let Some(description) = &embed.description {
+            html.push_str(&format!(
+                "<div class=\"embed-description\">{}</div>",
+                render_markdown(description, names)
+            ));
+        }
+        if !embed.fields.is_empty() {
+            html.push_str("<div class=\"embed-fields\">");
+            for field in &embed.fields {
+                html.push_str(&format!(
+                    "<div class=\"embed-field{}\"><div class=\"embed-field-name\">{}</div><div class=\"embed-field-value\">{}</div></div>",
+                    if field.inline { " inline" } else { "" },
+                    render_markdown(&field.name, names),
+                    render_markdown(&field.value, names)
+                ));
+            }
+            html.push_str("</div>");
+        }
+        let media = [
+            ("Image", &embed.image),
+            ("Thumbnail", &embed.thumbnail),
+            ("Video", &embed.video),
+        ];
+        for (label, media) in media {
+            if let Some(url) = media.as_ref().and_then(|media| media.url.as_ref()) {
+                html.push_str(&format!(
+                    "<div class=\"embed-media\">{}: {}</div>",
+                    label,
+                    optional_link(url, Some(url))
+                ));
+            }
+        }
+        let footer_text = embed
+            .footer
+            .as_ref()
+            .map(|footer| escape_html(&footer.text));
+        let footer_timestamp = embed
+            .timestamp
+            .map(|timestamp| format!("{} UTC", format_datetime(timestamp)));
+        let footer: Vec<String> = footer_text.into_iter().chain(footer_timestamp).collect();
+        if !footer.is_empty() {
+            html.push_str(&format!(
+                "<div class=\"embed-footer\">{}</div>",
+                footer.join(" &bull; ")
+            ));
+        }
+        html.push_str("</div>");
+    }
+}
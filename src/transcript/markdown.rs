@@ -0,0 +1,240 @@
+use super::{format_unix_timestamp, MentionNames};
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+// Renders the Discord flavour of markdown into HTML. Code, mentions, emojis, timestamps and links
+// are rendered first and swapped for placeholders, so that the formatting markers found inside
+// them (e.g. the underscores of a url) are left alone.
+
+const PLACEHOLDER: char = '\u{1}';
+
+static CODE_BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)```(?:([A-Za-z0-9_+.#-]+)?\n)?(.*?)```").unwrap());
+static INLINE_CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"``([^`]+?)``|`([^`]+)`").unwrap());
+// The patterns below match text that's already escaped, `<` being `&lt;` and so on.
+static USER_MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"&lt;@!?(\d+)&gt;").unwrap());
+static ROLE_MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&lt;@&amp;(\d+)&gt;").unwrap());
+static CHANNEL_MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"&lt;#(\d+)&gt;").unwrap());
+static EVERYONE_MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"@(everyone|here)\b").unwrap());
+static CUSTOM_EMOJI: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&lt;a?:(\w+):(\d+)&gt;").unwrap());
+static TIMESTAMP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&lt;t:(-?\d+)(?::([tTdDfFR]))?&gt;").unwrap());
+static MASKED_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[([^\[\]]+)\]\((https?://[^\s)]+)\)").unwrap());
+// `<https://...>` links are only kept from being embedded by Discord.
+static ANGLE_BRACKET_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&lt;(https?://\S+?)&gt;").unwrap());
+static LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"https?://(?:[^\s&\x01]|&amp;)+").unwrap());
+static FORMATTING: LazyLock<[(Regex, &str); 7]> = LazyLock::new(|| {
+    [
+        (r"\*\*(.+?)\*\*", "<strong>$1</strong>"),
+        (r"__(.+?)__", "<u>$1</u>"),
+        (r"\*(.+?)\*", "<em>$1</em>"),
+        (r"\b_(.+?)_\b", "<em>$1</em>"),
+        (r"~~(.+?)~~", "<s>$1</s>"),
+        (r"\|\|(.+?)\|\|", "<span class=\"spoiler\">$1</span>"),
+        (r"^-# (.*)$", "<small class=\"subtext\">$1</small>"),
+    ]
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+});
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(#{1,3}) (.+)$").unwrap());
+static PLACEHOLDER_TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x01(\d+)\x01").unwrap());
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+// Pieces of rendered HTML taken out of the text until it's fully rendered.
+#[derive(Default)]
+struct Placeholders(Vec<String>);
+
+impl Placeholders {
+    fn push(&mut self, html: String) -> String {
+        self.0.push(html);
+        format!("{0}{1}{0}", PLACEHOLDER, self.0.len() - 1)
+    }
+
+    // Placeholders can hold placeholders of their own, e.g. code in the text of a link.
+    fn restore(&self, text: &str) -> String {
+        PLACEHOLDER_TOKEN
+            .replace_all(text, |captures: &Captures| {
+                captures[1]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| self.0.get(index))
+                    .map(|html| self.restore(html))
+                    .unwrap_or_default()
+            })
+            .into_owned()
+    }
+}
+
+fn mention(text: String) -> String {
+    format!("<span class=\"mention\">{}</span>", escape_html(&text))
+}
+
+fn link(url: &str, text: &str) -> String {
+    format!("<a href=\"{}\" rel=\"noreferrer\">{}</a>", url, text)
+}
+
+pub(crate) fn render_markdown(content: &str, names: &MentionNames) -> String {
+    let mut placeholders = Placeholders::default();
+    let content = content.replace(PLACEHOLDER, "");
+
+    let content = CODE_BLOCK.replace_all(&content, |captures: &Captures| {
+        let code = escape_html(&captures[2]);
+        let html = match captures.get(1) {
+            Some(language) => format!(
+                "<pre><code class=\"language-{}\">{}</code></pre>",
+                escape_html(language.as_str()),
+                code
+            ),
+            None => format!("<pre><code>{}</code></pre>", code),
+        };
+        placeholders.push(html)
+    });
+    let content = INLINE_CODE.replace_all(&content, |captures: &Captures| {
+        let code = captures.get(1).or_else(|| captures.get(2)).unwrap();
+        placeholders.push(format!("<code>{}</code>", escape_html(code.as_str())))
+    });
+
+    let content = escape_html(&content);
+    let content = USER_MENTION.replace_all(&content, |captures: &Captures| {
+        let user_id = captures[1].parse().unwrap_or_default();
+        placeholders.push(mention(format!("@{}", names.user(user_id))))
+    });
+    let content = ROLE_MENTION.replace_all(&content, |captures: &Captures| {
+        let role_id = captures[1].parse().unwrap_or_default();
+        placeholders.push(mention(format!("@{}", names.role(role_id))))
+    });
+    let content = CHANNEL_MENTION.replace_all(&content, |captures: &Captures| {
+        let channel_id = captures[1].parse().unwrap_or_default();
+        placeholders.push(mention(format!("#{}", names.channel(channel_id))))
+    });
+    let content = EVERYONE_MENTION.replace_all(&content, |captures: &Captures| {
+        placeholders.push(mention(format!("@{}", &captures[1])))
+    });
+    let content = CUSTOM_EMOJI.replace_all(&content, |captures: &Captures| {
+        placeholders.push(format!(
+            "<span class=\"emoji\" title=\"{}\">:{}:</span>",
+            &captures[2], &captures[1]
+        ))
+    });
+    let content = TIMESTAMP.replace_all(&content, |captures: &Captures| {
+        let style = captures.get(2).map(|style| style.as_str());
+        match captures[1]
+            .parse()
+            .ok()
+            .and_then(|seconds| format_unix_timestamp(seconds, style))
+        {
+            Some(timestamp) => {
+                placeholders.push(format!("<span class=\"timestamp\">{}</span>", timestamp))
+            }
+            None => captures[0].to_string(),
+        }
+    });
+    let content = MASKED_LINK.replace_all(&content, |captures: &Captures| {
+        placeholders.push(link(&captures[2], &captures[1]))
+    });
+    let content = ANGLE_BRACKET_LINK.replace_all(&content, |captures: &Captures| {
+        placeholders.push(link(&captures[1], &captures[1]))
+    });
+    let content = LINK.replace_all(&content, |captures: &Captures| {
+        placeholders.push(link(&captures[0], &captures[0]))
+    });
+
+    let html = render_lines(&content);
+    placeholders.restore(&html)
+}
+
+// A line of text, or a quote or heading that doesn't need a line break around it.
+enum RenderedLine {
+    Text(String),
+    Block(String),
+}
+
+// Formatting applies within a line, quotes and headings to whole lines.
+fn render_lines(content: &str) -> String {
+    let mut lines = Vec::new();
+    let mut quoted_lines: Vec<String> = Vec::new();
+    let mut quote_rest = false;
+    let end_quote = |lines: &mut Vec<RenderedLine>, quoted_lines: &mut Vec<String>| {
+        if !quoted_lines.is_empty() {
+            lines.push(RenderedLine::Block(format!(
+                "<blockquote>{}</blockquote>",
+                quoted_lines.join("<br>")
+            )));
+            quoted_lines.clear();
+        }
+    };
+    for line in content.split('\n') {
+        // `>>> ` quotes the rest of the message, `> ` a single line.
+        let quoted_line = if quote_rest {
+            Some(line)
+        } else if let Some(rest) = line.strip_prefix("&gt;&gt;&gt; ") {
+            quote_rest = true;
+            Some(rest)
+        } else if line == "&gt;" {
+            Some("")
+        } else {
+            line.strip_prefix("&gt; ")
+        };
+        if let Some(quoted_line) = quoted_line {
+            quoted_lines.push(format_line(quoted_line));
+            continue;
+        }
+        end_quote(&mut lines, &mut quoted_lines);
+        match HEADING.captures(line) {
+            Some(captures) => lines.push(RenderedLine::Block(format!(
+                "<h{0} class=\"heading\">{1}</h{0}>",
+                captures[1].len(),
+                format_line(&captures[2])
+            ))),
+            None => lines.push(RenderedLine::Text(format_line(line))),
+        }
+    }
+    end_quote(&mut lines, &mut quoted_lines);
+
+    let mut html = String::new();
+    let mut previous_line_is_text = false;
+    for line in lines {
+        match line {
+            RenderedLine::Text(text) => {
+                if previous_line_is_text {
+                    html.push_str("<br>");
+                }
+                html.push_str(&text);
+                previous_line_is_text = true;
+            }
+            RenderedLine::Block(block) => {
+                html.push_str(&block);
+                previous_line_is_text = false;
+            }
+        }
+    }
+    html
+}
+
+fn format_line(line: &str) -> String {
+    FORMATTING
+        .iter()
+        .fold(line.to_string(), |line, (pattern, replacement)| {
+            pattern.replace_all(&line, *replacement).into_owned()
+        })
+}
@@ -0,0 +1,299 @@
+// Human readable transcripts of an archived channel, in the spirit of DiscordChatExporter.
+
+pub mod html;
+mod markdown;
+pub mod text;
+
+use crate::discord_api::models::{Emoji, User};
+use crate::discord_api::Message;
+use crate::utils::snowflake::datetime_from_snowflake;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::LazyLock;
+
+// Consecutive messages of an author share a header unless they're further apart than this.
+const GROUPING_WINDOW_MINUTES: i64 = 7;
+
+// Code is matched too so that the mentions inside of it are left as they are, like Discord does.
+static MENTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?s)```.*?```|`[^`]+`|<(@!?|@&|#)(\d+)>|<a?:(\w+):\d+>|<t:(-?\d+)(?::([tTdDfFR]))?>",
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TranscriptFormat {
+    // Self-contained pages, see `html`.
+    Html,
+    Markdown,
+    Text,
+}
+
+impl TranscriptFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Html => "html",
+            TranscriptFormat::Markdown => "md",
+            TranscriptFormat::Text => "txt",
+        }
+    }
+}
+
+// The messages of a channel, and of its threads, oldest first.
+pub struct Transcript {
+    title: String,
+    messages: Vec<Message>,
+    message_indexes: HashMap<u64, usize>,
+    names: MentionNames,
+}
+
+impl Transcript {
+    // A message archived more than once, e.g. by an incremental scrape after it was edited, is
+    // only kept in its last version.
+    pub fn new<S: Into<String>>(title: S, messages: Vec<Message>) -> Self {
+        let messages: Vec<Message> = messages
+            .into_iter()
+            .map(|message| (message.message_id, message))
+            .collect::<BTreeMap<_, _>>()
+            .into_values()
+            .collect();
+        let message_indexes = messages
+            .iter()
+            .enumerate()
+            .map(|(index, message)| (message.message_id, index))
+            .collect();
+        let names = MentionNames::from_messages(&messages);
+        Self {
+            title: title.into(),
+            messages,
+            message_indexes,
+            names,
+        }
+    }
+
+    pub fn with_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    // Names `<#id>` mentions of the channel, the archives don't hold channel names.
+    pub fn with_channel_name<S: Into<String>>(mut self, channel_id: u64, name: S) -> Self {
+        self.names.channels.insert(channel_id, name.into());
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn names(&self) -> &MentionNames {
+        &self.names
+    }
+
+    pub fn message(&self, message_id: u64) -> Option<&Message> {
+        self.message_index(message_id)
+            .map(|index| &self.messages[index])
+    }
+
+    // Position of a message in the transcript, oldest first.
+    pub fn message_index(&self, message_id: u64) -> Option<usize> {
+        self.message_indexes.get(&message_id).copied()
+    }
+}
+
+// When a message was sent, read from its id when the archive doesn't have it.
+pub fn message_timestamp(message: &Message) -> DateTime<Utc> {
+    message
+        .timestamp
+        .unwrap_or_else(|| datetime_from_snowflake(message.message_id))
+}
+
+// Consecutive messages shown under a single author header.
+pub struct MessageGroup<'a> {
+    pub messages: Vec<&'a Message>,
+}
+
+impl<'a> MessageGroup<'a> {
+    pub fn first(&self) -> &'a Message {
+        self.messages[0]
+    }
+
+    pub fn day(&self) -> NaiveDate {
+        message_timestamp(self.first()).date_naive()
+    }
+}
+
+// A new group starts with another author, channel or day, with a reply, or after a pause.
+pub fn group_messages(messages: &[Message]) -> Vec<MessageGroup<'_>> {
+    let mut groups: Vec<MessageGroup> = Vec::new();
+    for message in messages {
+        match groups.last_mut() {
+            Some(group) if !starts_group(group.messages[group.messages.len() - 1], message) => {
+                group.messages.push(message)
+            }
+            _ => groups.push(MessageGroup {
+                messages: vec![message],
+            }),
+        }
+    }
+    groups
+}
+
+fn starts_group(previous_message: &Message, message: &Message) -> bool {
+    let previous_timestamp = message_timestamp(previous_message);
+    let timestamp = message_timestamp(message);
+    previous_message.author_id != message.author_id
+        || previous_message.channel_id != message.channel_id
+        || message.reply_to_message_id().is_some()
+        || previous_timestamp.date_naive() != timestamp.date_naive()
+        || timestamp - previous_timestamp > TimeDelta::minutes(GROUPING_WINDOW_MINUTES)
+}
+
+// The global name of a user when they have one, their username otherwise.
+pub fn display_name(user: &User) -> &str {
+    user.global_name
+        .as_deref()
+        .filter(|global_name| !global_name.is_empty())
+        .unwrap_or(&user.username)
+}
+
+// Names of the users, channels and roles that mentions refer to. Unknown ones are shown by id.
+#[derive(Debug, Default, Clone)]
+pub struct MentionNames {
+    users: HashMap<u64, String>,
+    channels: HashMap<u64, String>,
+    roles: HashMap<u64, String>,
+}
+
+impl MentionNames {
+    // The authors and mentioned users of the messages, the latest name of each user winning.
+    pub fn from_messages(messages: &[Message]) -> Self {
+        let mut names = Self::default();
+        for message in messages {
+            for user in message.author.iter().chain(&message.mentions) {
+                if !user.username.is_empty() {
+                    names.users.insert(user.id, display_name(user).to_string());
+                }
+            }
+        }
+        names
+    }
+
+    pub fn user(&self, user_id: u64) -> String {
+        self.users
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_else(|| user_id.to_string())
+    }
+
+    pub fn channel(&self, channel_id: u64) -> String {
+        self.channels
+            .get(&channel_id)
+            .cloned()
+            .unwrap_or_else(|| channel_id.to_string())
+    }
+
+    pub fn role(&self, role_id: u64) -> String {
+        self.roles
+            .get(&role_id)
+            .cloned()
+            .unwrap_or_else(|| role_id.to_string())
+    }
+
+    // Replaces the `<@id>`, `<@&id>` and `<#id>` mentions of a message with names, custom emojis
+    // with their `:name:` and timestamps with dates.
+    pub fn resolve(&self, content: &str) -> String {
+        MENTION
+            .replace_all(content, |captures: &Captures| {
+                let id = || captures[2].parse().unwrap_or_default();
+                if let Some(kind) = captures.get(1) {
+                    match kind.as_str() {
+                        "#" => format!("#{}", self.channel(id())),
+                        "@&" => format!("@{}", self.role(id())),
+                        _ => format!("@{}", self.user(id())),
+                    }
+                } else if let Some(emoji_name) = captures.get(3) {
+                    format!(":{}:", emoji_name.as_str())
+                } else if let Some(seconds) = captures.get(4) {
+                    let style = captures.get(5).map(|style| style.as_str());
+                    seconds
+                        .as_str()
+                        .parse()
+                        .ok()
+                        .and_then(|seconds| format_unix_timestamp(seconds, style))
+                        .unwrap_or_else(|| captures[0].to_string())
+                } else {
+                    captures[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+
+    pub fn author(&self, message: &Message) -> String {
+        match &message.author {
+            Some(author) if !author.username.is_empty() => display_name(author).to_string(),
+            _ => self.user(message.author_id),
+        }
+    }
+}
+
+// `<t:1714643052:R>` markup, relative styles are shown as full dates since a transcript is read
+// long after it was written.
+pub(crate) fn format_unix_timestamp(seconds: i64, style: Option<&str>) -> Option<String> {
+    let timestamp = DateTime::from_timestamp(seconds, 0)?;
+    let format = match style {
+        Some("t") => "%H:%M",
+        Some("T") => "%H:%M:%S",
+        Some("d") => "%Y-%m-%d",
+        Some("D") => "%-d %B %Y",
+        Some("F") => "%A, %-d %B %Y %H:%M",
+        _ => "%-d %B %Y %H:%M",
+    };
+    Some(format!("{} UTC", timestamp.format(format)))
+}
+
+// Human readable attachment sizes, e.g. `1.5 MB`.
+pub(crate) fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if size < 1000 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+// Messages without content of their own, e.g. joins and pins, shown as a line of text.
+pub(crate) fn system_message_text(kind: u8) -> Option<&'static str> {
+    match kind {
+        1 => Some("added someone to the group"),
+        2 => Some("removed someone from the group"),
+        4 => Some("changed the channel name"),
+        6 => Some("pinned a message to this channel"),
+        7 => Some("joined the server"),
+        8 => Some("boosted the server"),
+        18 => Some("started a thread"),
+        _ => None,
+    }
+}
+
+// Unicode emojis as themselves, custom ones by name.
+pub(crate) fn emoji_text(emoji: &Emoji) -> String {
+    match (&emoji.id, &emoji.name) {
+        (Some(_), Some(name)) => format!(":{}:", name),
+        (None, Some(name)) => name.clone(),
+        (Some(id), None) => format!(":{}:", id),
+        (None, None) => String::new(),
+    }
+}
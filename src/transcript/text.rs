@@ -0,0 +1,214 @@
+use super::{
+    emoji_text, format_size, group_messages, message_timestamp, system_message_text, MessageGroup,
+    Transcript,
+};
+use crate::discord_api::models::Embed;
+use crate::discord_api::Message;
+
+// Single file transcripts for quick reads and for pasting into tickets. Message contents are kept
+// as they were written, Discord markdown being close enough to Markdown, with their mentions
+// resolved to names.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Markdown,
+    PlainText,
+}
+
+pub fn render_markdown(transcript: &Transcript) -> String {
+    render(transcript, Style::Markdown)
+}
+
+pub fn render_plain_text(transcript: &Transcript) -> String {
+    render(transcript, Style::PlainText)
+}
+
+// Backslashes the characters that would format a name, e.g. the underscores of `snake_case`.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        if "\\`*_~|[]<>#".contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+fn render(transcript: &Transcript, style: Style) -> String {
+    let mut text = match style {
+        Style::Markdown => format!("# {}\n\n", escape_markdown(transcript.title())),
+        Style::PlainText => format!(
+            "{}\n{}\n\n",
+            transcript.title(),
+            "=".repeat(transcript.title().chars().count())
+        ),
+    };
+    text.push_str(&format!(
+        "{} messages, times are in UTC.\n",
+        transcript.messages().len()
+    ));
+    let mut previous_day = None;
+    for group in group_messages(transcript.messages()) {
+        let day = group.day();
+        if previous_day != Some(day) {
+            let day_title = day.format("%A, %-d %B %Y");
+            match style {
+                Style::Markdown => text.push_str(&format!("\n## {}\n", day_title)),
+                Style::PlainText => text.push_str(&format!("\n--- {} ---\n", day_title)),
+            }
+            previous_day = Some(day);
+        }
+        render_group(&mut text, transcript, &group, style);
+    }
+    text
+}
+
+fn render_group(text: &mut String, transcript: &Transcript, group: &MessageGroup, style: Style) {
+    let names = transcript.names();
+    let first_message = group.first();
+    let name = |name: String| match style {
+        Style::Markdown => format!("**{}**", escape_markdown(&name)),
+        Style::PlainText => name,
+    };
+    let mut details = Vec::new();
+    if let Some(reply_to_message_id) = first_message.reply_to_message_id() {
+        details.push(match transcript.message(reply_to_message_id) {
+            Some(replied_message) => format!("replying to {}", name(names.author(replied_message))),
+            None => format!("replying to message {}", reply_to_message_id),
+        });
+    }
+    if first_message.parent_channel_id.is_some() {
+        details.push(format!(
+            "in thread #{}",
+            names.channel(first_message.channel_id)
+        ));
+    }
+    let time = message_timestamp(first_message).format("%H:%M");
+    let author = name(names.author(first_message));
+    match style {
+        Style::Markdown => {
+            text.push_str(&format!("\n{} · {}", author, time));
+            for detail in details {
+                text.push_str(&format!(" · {}", detail));
+            }
+            text.push('\n');
+        }
+        Style::PlainText => {
+            text.push_str(&format!("\n[{}] {}", time, author));
+            for detail in details {
+                text.push_str(&format!(", {}", detail));
+            }
+            text.push_str(":\n");
+        }
+    }
+    for message in &group.messages {
+        render_message(text, transcript, message, style);
+    }
+}
+
+fn render_message(text: &mut String, transcript: &Transcript, message: &Message, style: Style) {
+    let mut content = match system_message_text(message.kind) {
+        Some(system_text) if message.message.is_empty() => match style {
+            Style::Markdown => format!("*{}*", system_text),
+            Style::PlainText => format!("({})", system_text),
+        },
+        _ => transcript.names().resolve(&message.message),
+    };
+    if message.edited_timestamp.is_some() {
+        // Kept out of a trailing code block.
+        if content.ends_with("```") {
+            content.push('\n');
+        } else if !content.is_empty() {
+            content.push(' ');
+        }
+        content.push_str(match style {
+            Style::Markdown => "*(edited)*",
+            Style::PlainText => "(edited)",
+        });
+    }
+
+    // What came with the message, e.g. `("Attachment", "[a.png](https://...) (1.5 MB)")`.
+    let mut extras: Vec<(&str, String)> = Vec::new();
+    for attachment in &message.attachments {
+        extras.push((
+            "Attachment",
+            match style {
+                Style::Markdown => format!(
+                    "[{}]({}) ({})",
+                    escape_markdown(&attachment.filename),
+                    attachment.url,
+                    format_size(attachment.size)
+                ),
+                Style::PlainText => format!(
+                    "{} ({}) {}",
+                    attachment.filename,
+                    format_size(attachment.size),
+                    attachment.url
+                ),
+            },
+        ));
+    }
+    for embed in &message.embeds {
+        if let Some(embed_text) = embed_text(embed, style) {
+            extras.push(("Embed", embed_text));
+        }
+    }
+    for sticker in &message.sticker_items {
+        extras.push(("Sticker", sticker.name.clone()));
+    }
+    if !message.reactions.is_empty() {
+        let reactions: Vec<String> = message
+            .reactions
+            .iter()
+            .map(|reaction| format!("{} {}", emoji_text(&reaction.emoji), reaction.count))
+            .collect();
+        extras.push(("Reactions", reactions.join(", ")));
+    }
+
+    match style {
+        // Every message is a paragraph of its own, followed by a list of what came with it.
+        Style::Markdown => {
+            if !content.is_empty() {
+                text.push_str(&format!("\n{}\n", content));
+            }
+            if !extras.is_empty() {
+                text.push('\n');
+                for (label, extra) in extras {
+                    text.push_str(&format!("- {}: {}\n", label, extra));
+                }
+            }
+        }
+        // Indented under the author, with what came with it between brackets.
+        Style::PlainText => {
+            for line in content.lines() {
+                text.push_str(&format!("  {}\n", line));
+            }
+            for (label, extra) in extras {
+                text.push_str(&format!("  [{}: {}]\n", label.to_lowercase(), extra));
+            }
+        }
+    }
+}
+
+// The title of an embed, or whatever names it, along with its url.
+fn embed_text(embed: &Embed, style: Style) -> Option<String> {
+    let title = embed
+        .title
+        .clone()
+        .or_else(|| embed.author.as_ref().map(|author| author.name.clone()))
+        .or_else(|| {
+            embed
+                .description
+                .as_ref()
+                .and_then(|description| description.lines().next())
+                .map(str::to_string)
+        })
+        .or_else(|| embed.url.clone())?;
+    Some(match (style, &embed.url) {
+        (Style::Markdown, Some(url)) => format!("[{}]({})", escape_markdown(&title), url),
+        (Style::Markdown, None) => escape_markdown(&title),
+        (Style::PlainText, Some(url)) if *url != title => format!("{} {}", title, url),
+        (Style::PlainText, _) => title,
+    })
+}
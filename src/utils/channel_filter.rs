@@ -0,0 +1,116 @@
+use crate::discord_api::models::{channel_kind, Channel};
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::HashMap;
+
+// The kinds of guild channels that hold messages, either directly or through their posts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScrapableChannelType {
+    Text,
+    Announcement,
+    Forum,
+    Voice,
+}
+
+impl ScrapableChannelType {
+    pub const ALL: [ScrapableChannelType; 4] = [
+        ScrapableChannelType::Text,
+        ScrapableChannelType::Announcement,
+        ScrapableChannelType::Forum,
+        ScrapableChannelType::Voice,
+    ];
+
+    pub fn from_kind(kind: u8) -> Option<Self> {
+        match kind {
+            channel_kind::GUILD_TEXT => Some(ScrapableChannelType::Text),
+            channel_kind::GUILD_ANNOUNCEMENT => Some(ScrapableChannelType::Announcement),
+            channel_kind::GUILD_FORUM | channel_kind::GUILD_MEDIA => {
+                Some(ScrapableChannelType::Forum)
+            }
+            channel_kind::GUILD_VOICE | channel_kind::GUILD_STAGE_VOICE => {
+                Some(ScrapableChannelType::Voice)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Empty include lists mean everything is included, excludes always win over includes. Categories
+// can be given by id or by name.
+#[derive(Debug, Clone)]
+pub struct ChannelFilter {
+    pub include_names: Vec<Regex>,
+    pub exclude_names: Vec<Regex>,
+    pub include_categories: Vec<String>,
+    pub exclude_categories: Vec<String>,
+    pub channel_types: Vec<ScrapableChannelType>,
+}
+
+impl Default for ChannelFilter {
+    fn default() -> Self {
+        Self {
+            include_names: Vec::new(),
+            exclude_names: Vec::new(),
+            include_categories: Vec::new(),
+            exclude_categories: Vec::new(),
+            channel_types: ScrapableChannelType::ALL.to_vec(),
+        }
+    }
+}
+
+impl ChannelFilter {
+    // Keeps the channels of `guild_channels` that should be scraped, in their display order.
+    pub fn apply(&self, guild_channels: Vec<Channel>) -> Vec<Channel> {
+        let category_names: HashMap<u64, String> = guild_channels
+            .iter()
+            .filter(|channel| channel.kind == channel_kind::GUILD_CATEGORY)
+            .filter_map(|channel| Some((channel.id, channel.name.clone()?)))
+            .collect();
+        let mut channels: Vec<Channel> = guild_channels
+            .into_iter()
+            .filter(|channel| self.matches(channel, &category_names))
+            .collect();
+        channels.sort_by_key(|channel| (channel.parent_id, channel.position));
+        channels
+    }
+
+    fn matches(&self, channel: &Channel, category_names: &HashMap<u64, String>) -> bool {
+        let Some(channel_type) = ScrapableChannelType::from_kind(channel.kind) else {
+            return false;
+        };
+        if !self.channel_types.contains(&channel_type) {
+            return false;
+        }
+
+        let name = channel.name.as_deref().unwrap_or_default();
+        if self
+            .exclude_names
+            .iter()
+            .any(|pattern| pattern.is_match(name))
+        {
+            return false;
+        }
+        if !self.include_names.is_empty()
+            && !self
+                .include_names
+                .iter()
+                .any(|pattern| pattern.is_match(name))
+        {
+            return false;
+        }
+
+        let matches_category = |category: &String| match channel.parent_id {
+            Some(parent_id) => {
+                category == &parent_id.to_string()
+                    || category_names
+                        .get(&parent_id)
+                        .is_some_and(|category_name| category_name.eq_ignore_ascii_case(category))
+            }
+            None => false,
+        };
+        if self.exclude_categories.iter().any(matches_category) {
+            return false;
+        }
+        self.include_categories.is_empty() || self.include_categories.iter().any(matches_category)
+    }
+}
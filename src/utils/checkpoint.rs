@@ -0,0 +1,35 @@
+use crate::discord_api::MessagePage;
+use serde::{Deserialize, Serialize};
+
+// How far the scrape of a channel got, so an interrupted scrape can continue where it stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrapeCheckpoint {
+    pub channel_id: u64,
+    pub oldest_message_id: Option<u64>,
+    pub newest_message_id: Option<u64>,
+    pub completed: bool,
+}
+
+impl ScrapeCheckpoint {
+    pub fn new(channel_id: u64) -> Self {
+        Self {
+            channel_id,
+            oldest_message_id: None,
+            newest_message_id: None,
+            completed: false,
+        }
+    }
+
+    // For pages fetched while walking backwards through the history.
+    pub fn record_older_page(&mut self, message_page: &MessagePage) {
+        if message_page.oldest_message_id.is_some() {
+            self.oldest_message_id = message_page.oldest_message_id;
+        }
+        self.newest_message_id = self.newest_message_id.max(message_page.newest_message_id);
+    }
+
+    // For pages fetched while catching up with new messages, the oldest cursor stays untouched.
+    pub fn record_newer_page(&mut self, message_page: &MessagePage) {
+        self.newest_message_id = self.newest_message_id.max(message_page.newest_message_id);
+    }
+}
@@ -0,0 +1,79 @@
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use clap::ValueEnum;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncWriteExt, BufReader};
+
+// Compressed JSONL archives, e.g. `general.jsonl.gz`. They're written as a series of gzip members
+// or zstd frames, one per page of messages, so that an archive can be appended to by a resumed
+// scrape. A page cut off half way through its write, e.g. by a crash, isn't recovered: reading
+// the archive then fails with `UnexpectedEof`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    // Read from the extension of a path, `None` for uncompressed files.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+
+    // Compresses bytes into a gzip member or a zstd frame of their own.
+    pub async fn compress(&self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzipEncoder::new(Vec::new());
+                encoder.write_all(bytes).await?;
+                encoder.shutdown().await?;
+                Ok(encoder.into_inner())
+            }
+            Compression::Zstd => {
+                let mut encoder = ZstdEncoder::new(Vec::new());
+                encoder.write_all(bytes).await?;
+                encoder.shutdown().await?;
+                Ok(encoder.into_inner())
+            }
+        }
+    }
+}
+
+// `general.jsonl.gz` -> `general.jsonl`, uncompressed paths are returned as they are.
+pub fn uncompressed_path(path: &Path) -> PathBuf {
+    match Compression::from_path(path) {
+        Some(_) => path.with_extension(""),
+        None => path.to_path_buf(),
+    }
+}
+
+// Opens a file for reading, decompressing it on the fly when its extension says so.
+pub async fn open_reader(path: &Path) -> io::Result<Box<dyn AsyncBufRead + Send + Unpin>> {
+    let reader = BufReader::new(File::open(path).await?);
+    Ok(match Compression::from_path(path) {
+        Some(Compression::Gzip) => {
+            let mut decoder = GzipDecoder::new(reader);
+            decoder.multiple_members(true);
+            Box::new(BufReader::new(decoder))
+        }
+        Some(Compression::Zstd) => {
+            let mut decoder = ZstdDecoder::new(reader);
+            decoder.multiple_members(true);
+            Box::new(BufReader::new(decoder))
+        }
+        None => Box::new(reader),
+    })
+}
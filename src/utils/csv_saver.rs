@@ -0,0 +1,233 @@
+use crate::discord_api::{Message, RejectedMessage};
+use crate::utils::checkpoint::ScrapeCheckpoint;
+use crate::utils::message_saver::{sibling_path, MessageSaver, RejectedMessagesFile};
+use crate::utils::snowflake::datetime_from_snowflake;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use color_eyre::eyre::Result;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+// Spreadsheets show these without any parsing, always in UTC.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// Named like the columns of the SQL schema, both on the command line and in the header row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum CsvColumn {
+    MessageId,
+    ChannelId,
+    ParentChannelId,
+    AuthorId,
+    AuthorName,
+    // When the message was sent, read from its id.
+    Timestamp,
+    EditedTimestamp,
+    Message,
+    HasMedia,
+    // The urls of the attachments, separated by spaces.
+    Attachments,
+    ReplyToMessageId,
+    MessageType,
+    Pinned,
+}
+
+impl CsvColumn {
+    pub const DEFAULT: [CsvColumn; 6] = [
+        CsvColumn::MessageId,
+        CsvColumn::Timestamp,
+        CsvColumn::AuthorId,
+        CsvColumn::AuthorName,
+        CsvColumn::Message,
+        CsvColumn::Attachments,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CsvColumn::MessageId => "message_id",
+            CsvColumn::ChannelId => "channel_id",
+            CsvColumn::ParentChannelId => "parent_channel_id",
+            CsvColumn::AuthorId => "author_id",
+            CsvColumn::AuthorName => "author_name",
+            CsvColumn::Timestamp => "timestamp",
+            CsvColumn::EditedTimestamp => "edited_timestamp",
+            CsvColumn::Message => "message",
+            CsvColumn::HasMedia => "has_media",
+            CsvColumn::Attachments => "attachments",
+            CsvColumn::ReplyToMessageId => "reply_to_message_id",
+            CsvColumn::MessageType => "message_type",
+            CsvColumn::Pinned => "pinned",
+        }
+    }
+
+    fn value(&self, message: &Message) -> String {
+        let optional =
+            |value: Option<u64>| value.map(|value| value.to_string()).unwrap_or_default();
+        match self {
+            CsvColumn::MessageId => message.message_id.to_string(),
+            CsvColumn::ChannelId => message.channel_id.to_string(),
+            CsvColumn::ParentChannelId => optional(message.parent_channel_id),
+            CsvColumn::AuthorId => message.author_id.to_string(),
+            CsvColumn::AuthorName => message
+                .author
+                .as_ref()
+                .map(|author| author.username.clone())
+                .unwrap_or_default(),
+            CsvColumn::Timestamp => format_timestamp(datetime_from_snowflake(message.message_id)),
+            CsvColumn::EditedTimestamp => message
+                .edited_timestamp
+                .map(format_timestamp)
+                .unwrap_or_default(),
+            CsvColumn::Message => message.message.clone(),
+            CsvColumn::HasMedia => message.has_media.to_string(),
+            CsvColumn::Attachments => message
+                .attachments
+                .iter()
+                .map(|attachment| attachment.url.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            CsvColumn::ReplyToMessageId => optional(message.reply_to_message_id()),
+            CsvColumn::MessageType => message.kind.to_string(),
+            CsvColumn::Pinned => message.pinned.to_string(),
+        }
+    }
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.format(TIMESTAMP_FORMAT).to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub columns: Vec<CsvColumn>,
+    pub header: bool,
+    // Prefixes cells starting with `=`, `+`, `-`, `@`, a tab or a carriage return with a `'`, so
+    // that spreadsheets show a message like `=HYPERLINK(...)` as text instead of evaluating it as
+    // a formula.
+    pub escape_formulas: bool,
+}
+
+impl CsvOptions {
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: '\t',
+            ..Self::default()
+        }
+    }
+
+    // `csv`, or `tsv` when the values are separated by tabs.
+    pub fn extension(&self) -> &'static str {
+        if self.delimiter == '\t' {
+            "tsv"
+        } else {
+            "csv"
+        }
+    }
+
+    // Fields holding the delimiter, quotes or line breaks are quoted, with their quotes doubled,
+    // so multi-line messages stay in one cell.
+    fn escape(&self, field: &str) -> String {
+        let field = if self.escape_formulas && field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+            format!("'{}", field)
+        } else {
+            field.to_string()
+        };
+        if field.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    }
+
+    fn line<'a, I: IntoIterator<Item = &'a str>>(&self, fields: I) -> String {
+        let mut line = fields
+            .into_iter()
+            .map(|field| self.escape(field))
+            .collect::<Vec<_>>()
+            .join(&self.delimiter.to_string());
+        line.push_str("\r\n");
+        line
+    }
+
+    pub fn header_line(&self) -> String {
+        self.line(self.columns.iter().map(CsvColumn::name))
+    }
+
+    pub fn message_line(&self, message: &Message) -> String {
+        let values: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| column.value(message))
+            .collect();
+        self.line(values.iter().map(String::as_str))
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            columns: CsvColumn::DEFAULT.to_vec(),
+            header: true,
+            escape_formulas: true,
+        }
+    }
+}
+
+// Writes the messages of a channel as CSV or TSV rows, the file is written anew by every scrape.
+// Rejected messages go to a JSONL file next to it.
+pub struct CsvSaver {
+    options: CsvOptions,
+    writer: BufWriter<File>,
+    rejected_messages_file: RejectedMessagesFile,
+}
+
+impl CsvSaver {
+    pub async fn new(path: &Path, options: CsvOptions) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path).await?);
+        if options.header {
+            writer.write_all(options.header_line().as_bytes()).await?;
+            writer.flush().await?;
+        }
+        Ok(Self {
+            options,
+            writer,
+            rejected_messages_file: RejectedMessagesFile::new(sibling_path(path, "rejected.jsonl")),
+        })
+    }
+}
+
+#[async_trait]
+impl MessageSaver for CsvSaver {
+    async fn save_messages(&mut self, messages: &[Message]) -> Result<()> {
+        for message in messages {
+            let line = self.options.message_line(message);
+            self.writer.write_all(line.as_bytes()).await?;
+        }
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn save_rejected_messages(
+        &mut self,
+        rejected_messages: &[RejectedMessage],
+    ) -> Result<()> {
+        self.rejected_messages_file.append(rejected_messages).await
+    }
+
+    // Every scrape writes a new file, there's nothing to resume from.
+    async fn load_checkpoint(&mut self, _channel_id: u64) -> Result<Option<ScrapeCheckpoint>> {
+        Ok(None)
+    }
+
+    async fn newest_archived_message_id(&mut self, _channel_id: u64) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    async fn save_checkpoint(&mut self, _checkpoint: &ScrapeCheckpoint) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,73 +1,970 @@
-use crate::discord_api::Message;
+use crate::discord_api::models::{Attachment, Channel, MessageReference, User};
+use crate::discord_api::{Message, RejectedMessage};
+use crate::utils::checkpoint::ScrapeCheckpoint;
+use crate::utils::compression::{self, Compression};
+use crate::utils::csv_saver::{CsvOptions, CsvSaver};
+use crate::utils::parquet_saver::ParquetSaver;
+use crate::utils::postgres_saver::PostgresSaver;
+use crate::utils::snowflake::snowflake_from_i64;
+use crate::utils::sql_schema::{self, MYSQL_COLUMNS_QUERY, MYSQL_MIGRATIONS};
+use crate::utils::sqlite_saver::SqliteSaver;
 use async_trait::async_trait;
-use color_eyre::eyre::Result;
-use tokio::io::{AsyncWriteExt, BufWriter};
-use tokio::fs::{File, OpenOptions};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use sqlx::{MySql, QueryBuilder, Transaction};
+use std::collections::HashSet;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufWriter};
+use tokio::fs::{self, File, OpenOptions};
 
 pub enum SaveTarget {
-    Jsonl,
+    // Plain, gzip or zstd compressed JSONL files.
+    Jsonl(Option<Compression>),
+    // MySQL, or PostgreSQL for `postgres://` and `postgresql://` urls.
     Sql(String),
+    Sqlite(PathBuf),
+    // One Parquet file per channel, with row groups of the given number of messages.
+    Parquet(usize),
+    // One CSV or TSV file per channel.
+    Csv(CsvOptions),
+}
+
+// A save target ready to hand out savers. Every channel gets its own JSONL file, while all the
+// channels saved into a database share one connection pool.
+pub enum ConnectedSaveTarget {
+    Jsonl(Option<Compression>),
+    Sql(SqlSaver),
+    Postgres(PostgresSaver),
+    Sqlite(PathBuf, SqliteSaver),
+    Parquet(usize),
+    Csv(CsvOptions),
+}
+
+impl SaveTarget {
+    pub async fn connect(&self) -> Result<ConnectedSaveTarget> {
+        match self {
+            SaveTarget::Jsonl(compression) => Ok(ConnectedSaveTarget::Jsonl(*compression)),
+            SaveTarget::Sql(database_url) if PostgresSaver::is_postgres_url(database_url) => Ok(
+                ConnectedSaveTarget::Postgres(PostgresSaver::new(database_url).await?),
+            ),
+            SaveTarget::Sql(database_url) => {
+                Ok(ConnectedSaveTarget::Sql(SqlSaver::new(database_url).await?))
+            }
+            SaveTarget::Sqlite(path) => Ok(ConnectedSaveTarget::Sqlite(
+                path.clone(),
+                SqliteSaver::new(path).await?,
+            )),
+            SaveTarget::Parquet(row_group_size) => Ok(ConnectedSaveTarget::Parquet(*row_group_size)),
+            SaveTarget::Csv(csv_options) => Ok(ConnectedSaveTarget::Csv(csv_options.clone())),
+        }
+    }
+}
+
+impl ConnectedSaveTarget {
+    pub async fn saver(
+        &self,
+        channel_id: u64,
+        channel_name: &str,
+    ) -> Result<Box<dyn MessageSaver + Send + Sync>> {
+        let file_stem = Self::file_stem(channel_id, channel_name);
+        match self {
+            ConnectedSaveTarget::Jsonl(compression) => {
+                fs::create_dir_all("storage").await?;
+                let path = Self::jsonl_path(&file_stem, *compression);
+                Self::adopt_earlier_jsonl_archive(&path, channel_id, channel_name, *compression)
+                    .await?;
+                Ok(Box::new(JsonlSaver::new(&path).await?))
+            }
+            ConnectedSaveTarget::Sql(sql_saver) => Ok(Box::new(sql_saver.clone())),
+            ConnectedSaveTarget::Postgres(postgres_saver) => Ok(Box::new(postgres_saver.clone())),
+            ConnectedSaveTarget::Sqlite(_, sqlite_saver) => Ok(Box::new(sqlite_saver.clone())),
+            ConnectedSaveTarget::Parquet(row_group_size) => {
+                fs::create_dir_all("storage").await?;
+                Ok(Box::new(
+                    ParquetSaver::new(&Self::parquet_path(&file_stem), *row_group_size).await?,
+                ))
+            }
+            ConnectedSaveTarget::Csv(csv_options) => {
+                fs::create_dir_all("storage").await?;
+                Ok(Box::new(
+                    CsvSaver::new(
+                        &Self::csv_path(&file_stem, csv_options),
+                        csv_options.clone(),
+                    )
+                    .await?,
+                ))
+            }
+        }
+    }
+
+    pub fn output_path(&self, channel_id: u64, channel_name: &str) -> Option<PathBuf> {
+        let file_stem = Self::file_stem(channel_id, channel_name);
+        match self {
+            ConnectedSaveTarget::Jsonl(compression) => {
+                Some(PathBuf::from(Self::jsonl_path(&file_stem, *compression)))
+            }
+            ConnectedSaveTarget::Sql(_) | ConnectedSaveTarget::Postgres(_) => None,
+            ConnectedSaveTarget::Sqlite(path, _) => Some(path.clone()),
+            ConnectedSaveTarget::Parquet(_) => Some(Self::parquet_path(&file_stem)),
+            ConnectedSaveTarget::Csv(csv_options) => Some(Self::csv_path(&file_stem, csv_options)),
+        }
+    }
+
+    // Reads back the messages of a channel and of its threads, oldest first. Only databases can be
+    // queried, the files written by the other targets are read through their own conversions.
+    pub async fn channel_messages(&self, channel_id: u64) -> Result<Vec<Message>> {
+        match self {
+            ConnectedSaveTarget::Sql(sql_saver) => sql_saver.channel_messages(channel_id).await,
+            ConnectedSaveTarget::Postgres(postgres_saver) => {
+                postgres_saver.channel_messages(channel_id).await
+            }
+            ConnectedSaveTarget::Sqlite(_, sqlite_saver) => {
+                sqlite_saver.channel_messages(channel_id).await
+            }
+            ConnectedSaveTarget::Jsonl(_)
+            | ConnectedSaveTarget::Parquet(_)
+            | ConnectedSaveTarget::Csv(_) => Err(eyre!(
+                "Messages can only be read back from a database"
+            )),
+        }
+    }
+
+    // Guilds often have several channels of the same name, e.g. `general` in every category, so
+    // the files of a channel are named after its id too: `storage/general-659069446438125570`.
+    fn file_stem(channel_id: u64, channel_name: &str) -> String {
+        format!("storage/{}-{}", channel_name, channel_id)
+    }
+
+    // An archive of the channel saved under a name it had before, or from before the files were
+    // named after channel ids, is moved to `path` along with its checkpoint and rejected messages,
+    // so that resuming and syncing carry on with it instead of scraping the channel again.
+    async fn adopt_earlier_jsonl_archive(
+        path: &str,
+        channel_id: u64,
+        channel_name: &str,
+        compression: Option<Compression>,
+    ) -> Result<()> {
+        if fs::try_exists(path).await? {
+            return Ok(());
+        }
+        let Some(earlier_path) =
+            Self::earlier_jsonl_archive(channel_id, channel_name, compression).await?
+        else {
+            return Ok(());
+        };
+        tracing::info!(
+            "Moving the archive `{}` of channel `{}` to `{}`.",
+            earlier_path,
+            channel_id,
+            path
+        );
+        for suffix in JsonlSaver::SIBLING_SUFFIXES {
+            rename_if_exists(
+                &JsonlSaver::previous_sibling_path(&earlier_path, suffix),
+                &JsonlSaver::previous_sibling_path(path, suffix),
+            )
+            .await?;
+            rename_if_exists(
+                &sibling_path(Path::new(&earlier_path), suffix),
+                &sibling_path(Path::new(path), suffix),
+            )
+            .await?;
+        }
+        fs::rename(&earlier_path, path).await?;
+        Ok(())
+    }
+
+    // `storage/<any name>-<channel_id>.jsonl`, or else `storage/<channel_name>.jsonl` as long as
+    // it only holds messages of the channel, since channels of the same name used to share it.
+    async fn earlier_jsonl_archive(
+        channel_id: u64,
+        channel_name: &str,
+        compression: Option<Compression>,
+    ) -> Result<Option<String>> {
+        let file_name_suffix = Self::jsonl_path(&format!("-{}", channel_id), compression);
+        let mut entries = fs::read_dir("storage").await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(file_name) = entry.file_name().to_str() {
+                if file_name.ends_with(&file_name_suffix) {
+                    return Ok(Some(format!("storage/{}", file_name)));
+                }
+            }
+        }
+        let legacy_path = Self::jsonl_path(&format!("storage/{}", channel_name), compression);
+        if fs::try_exists(&legacy_path).await?
+            && Self::holds_only_channel(&legacy_path, channel_id).await?
+        {
+            return Ok(Some(legacy_path));
+        }
+        Ok(None)
+    }
+
+    async fn holds_only_channel(path: &str, channel_id: u64) -> Result<bool> {
+        #[derive(Deserialize)]
+        struct ArchivedChannelIds {
+            channel_id: u64,
+            parent_channel_id: Option<u64>,
+        }
+
+        let mut lines = compression::open_reader(Path::new(path)).await?.lines();
+        while let Some(line) = lines.next_line().await? {
+            if let Ok(archived) = serde_json::from_str::<ArchivedChannelIds>(&line) {
+                if archived.channel_id != channel_id
+                    && archived.parent_channel_id != Some(channel_id)
+                {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn jsonl_path(file_stem: &str, compression: Option<Compression>) -> String {
+        match compression {
+            Some(compression) => format!("{}.jsonl.{}", file_stem, compression.extension()),
+            None => format!("{}.jsonl", file_stem),
+        }
+    }
+
+    fn parquet_path(file_stem: &str) -> PathBuf {
+        PathBuf::from(format!("{}.parquet", file_stem))
+    }
+
+    fn csv_path(file_stem: &str, csv_options: &CsvOptions) -> PathBuf {
+        PathBuf::from(format!("{}.{}", file_stem, csv_options.extension()))
+    }
 }
 
 #[async_trait]
 pub trait MessageSaver {
     async fn save_messages(&mut self, messages: &[Message]) -> Result<()>;
+    async fn save_rejected_messages(&mut self, rejected_messages: &[RejectedMessage]) -> Result<()>;
+    async fn load_checkpoint(&mut self, channel_id: u64) -> Result<Option<ScrapeCheckpoint>>;
+    async fn newest_archived_message_id(&mut self, channel_id: u64) -> Result<Option<u64>>;
+    async fn save_checkpoint(&mut self, checkpoint: &ScrapeCheckpoint) -> Result<()>;
+    // Records the channels and threads being scraped along with their guild, for savers that keep
+    // them next to the messages.
+    async fn save_channels(&mut self, _channels: &[Channel]) -> Result<()> {
+        Ok(())
+    }
+    // Records when the scrape of a channel started and how it ended, for savers that keep a
+    // history of their runs.
+    async fn start_scrape_run(&mut self, _channel_id: u64) -> Result<()> {
+        Ok(())
+    }
+    async fn finish_scrape_run(&mut self, _outcome: &ScrapeRunOutcome) -> Result<()> {
+        Ok(())
+    }
+    // Called once a channel is scraped, even when it failed half way, for savers that have to
+    // complete their output.
+    async fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct ScrapeRunOutcome {
+    pub saved_messages: u64,
+    pub rejected_messages: u64,
+    // Whether the scrape went through without failing.
+    pub completed: bool,
+}
+
+// The files kept next to an output are named after its whole file name, so that outputs of
+// different formats don't share them: `storage/general-1.parquet` + `rejected.jsonl` ->
+// `storage/general-1.parquet.rejected.jsonl`, `storage/general-1.jsonl.gz` + `checkpoint.json` ->
+// `storage/general-1.jsonl.gz.checkpoint.json`.
+pub(crate) fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling_path = path.as_os_str().to_owned();
+    sibling_path.push(".");
+    sibling_path.push(suffix);
+    PathBuf::from(sibling_path)
+}
+
+async fn rename_if_exists(from: &Path, to: &Path) -> Result<()> {
+    match fs::rename(from, to).await {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+// The quarantine file of a saver, only created once something gets rejected.
+pub(crate) struct RejectedMessagesFile {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl RejectedMessagesFile {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            writer: None,
+        }
+    }
+
+    pub async fn append(&mut self, rejected_messages: &[RejectedMessage]) -> Result<()> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self.writer.insert(BufWriter::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .await?,
+            )),
+        };
+        for rejected_message in rejected_messages {
+            let json_line = serde_json::to_string(rejected_message)? + "\n";
+            writer.write_all(json_line.as_bytes()).await?;
+        }
+        writer.flush().await?;
+        Ok(())
+    }
 }
 
 pub struct JsonlSaver {
+    path: String,
+    // Read from the extension of the path, e.g. `general.jsonl.zst`.
+    compression: Option<Compression>,
     writer: BufWriter<File>,
+    rejected_messages_file: RejectedMessagesFile,
+    checkpoint_path: PathBuf,
 }
 
 impl JsonlSaver {
+    const SIBLING_SUFFIXES: [&'static str; 2] = ["rejected.jsonl", "checkpoint.json"];
+
     pub async fn new(path: &str) -> Result<Self> {
+        Self::rename_previous_siblings(path).await?;
+        let writer = BufWriter::new(Self::open_for_append(path).await?);
+        Ok(Self {
+            path: path.to_string(),
+            compression: Compression::from_path(Path::new(path)),
+            writer,
+            rejected_messages_file: RejectedMessagesFile::new(sibling_path(
+                Path::new(path),
+                "rejected.jsonl",
+            )),
+            checkpoint_path: sibling_path(Path::new(path), "checkpoint.json"),
+        })
+    }
+
+    // The names the files next to an archive had before they kept its `.jsonl` extension:
+    // `storage/general.jsonl` -> `storage/general.rejected.jsonl`, `storage/general.jsonl.gz` ->
+    // `storage/general.gz.rejected.jsonl`.
+    fn previous_sibling_path(path: &str, suffix: &str) -> PathBuf {
+        let compression = Compression::from_path(Path::new(path));
+        let path = compression::uncompressed_path(Path::new(path));
+        let path = path.to_string_lossy();
+        let stem = path.strip_suffix(".jsonl").unwrap_or(&path);
+        PathBuf::from(match compression {
+            Some(compression) => format!("{}.{}.{}", stem, compression.extension(), suffix),
+            None => format!("{}.{}", stem, suffix),
+        })
+    }
+
+    // Checkpoints and rejected messages saved under their previous names are carried over.
+    async fn rename_previous_siblings(path: &str) -> Result<()> {
+        for suffix in Self::SIBLING_SUFFIXES {
+            let sibling_path = sibling_path(Path::new(path), suffix);
+            if !fs::try_exists(&sibling_path).await? {
+                rename_if_exists(&Self::previous_sibling_path(path, suffix), &sibling_path)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    // One checkpoint per channel or thread saved into the file.
+    async fn read_checkpoints(&self) -> Result<Vec<ScrapeCheckpoint>> {
+        match fs::read_to_string(&self.checkpoint_path).await {
+            Ok(checkpoints_json) => Ok(serde_json::from_str(&checkpoints_json)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn open_for_append(path: &str) -> Result<File> {
         let file = OpenOptions::new()
             .create(true)
             .append(true)
             .open(path)
             .await?;
-        let writer = BufWriter::new(file);
-        Ok(Self { writer })
+        Ok(file)
     }
 }
 
 #[async_trait]
 impl MessageSaver for JsonlSaver {
     async fn save_messages(&mut self, messages: &[Message]) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let mut json_lines = String::new();
         for message in messages {
-            let json_line = serde_json::to_string(message)? + "\n";
-            self.writer.write_all(json_line.as_bytes()).await?;
+            json_lines.push_str(&serde_json::to_string(message)?);
+            json_lines.push('\n');
+        }
+        match self.compression {
+            Some(compression) => {
+                let compressed = compression.compress(json_lines.as_bytes()).await?;
+                self.writer.write_all(&compressed).await?;
+            }
+            None => self.writer.write_all(json_lines.as_bytes()).await?,
+        }
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn save_rejected_messages(
+        &mut self,
+        rejected_messages: &[RejectedMessage],
+    ) -> Result<()> {
+        self.rejected_messages_file.append(rejected_messages).await
+    }
+
+    async fn load_checkpoint(&mut self, channel_id: u64) -> Result<Option<ScrapeCheckpoint>> {
+        let checkpoints = self.read_checkpoints().await?;
+        Ok(checkpoints
+            .into_iter()
+            .find(|checkpoint| checkpoint.channel_id == channel_id))
+    }
+
+    async fn newest_archived_message_id(&mut self, channel_id: u64) -> Result<Option<u64>> {
+        #[derive(Deserialize)]
+        struct ArchivedMessageIds {
+            channel_id: u64,
+            message_id: u64,
         }
+
         self.writer.flush().await?;
+        let mut lines = compression::open_reader(Path::new(&self.path)).await?.lines();
+        let mut newest_message_id = None;
+        while let Some(line) = lines.next_line().await? {
+            if let Ok(archived) = serde_json::from_str::<ArchivedMessageIds>(&line) {
+                if archived.channel_id == channel_id {
+                    newest_message_id = newest_message_id.max(Some(archived.message_id));
+                }
+            }
+        }
+        Ok(newest_message_id)
+    }
+
+    // Written to a temporary file first so a crash mid-write never leaves a corrupt checkpoint.
+    async fn save_checkpoint(&mut self, checkpoint: &ScrapeCheckpoint) -> Result<()> {
+        let mut checkpoints = self.read_checkpoints().await?;
+        match checkpoints
+            .iter_mut()
+            .find(|saved_checkpoint| saved_checkpoint.channel_id == checkpoint.channel_id)
+        {
+            Some(saved_checkpoint) => *saved_checkpoint = checkpoint.clone(),
+            None => checkpoints.push(checkpoint.clone()),
+        }
+        let temporary_path = sibling_path(&self.checkpoint_path, "tmp");
+        fs::write(&temporary_path, serde_json::to_string_pretty(&checkpoints)?).await?;
+        fs::rename(&temporary_path, &self.checkpoint_path).await?;
         Ok(())
     }
 }
 
+// Cloning shares the connection pool.
+#[derive(Clone)]
 pub struct SqlSaver {
     pool: sqlx::MySqlPool,
+    // The `scrape_runs` row of the channel being scraped.
+    scrape_run_id: Option<u64>,
 }
 
 impl SqlSaver {
+    // Creates or upgrades the tables when needed.
     pub async fn new(database_url: &str) -> Result<Self> {
         let pool = sqlx::MySqlPool::connect(database_url).await?;
-        Ok(Self { pool })
+        sql_schema::migrate(&pool, MYSQL_MIGRATIONS, MYSQL_COLUMNS_QUERY).await?;
+        Ok(Self {
+            pool,
+            scrape_run_id: None,
+        })
+    }
+}
+
+// JSON columns of a message, serialized up front since building a query can't fail.
+pub(crate) struct MessageJsonColumns {
+    pub attachments: String,
+    pub embeds: String,
+    pub reactions: String,
+    pub stickers: String,
+    pub raw: Option<String>,
+}
+
+impl MessageJsonColumns {
+    pub fn new(message: &Message) -> Result<Self> {
+        Ok(Self {
+            attachments: serde_json::to_string(&message.attachments)?,
+            embeds: serde_json::to_string(&message.embeds)?,
+            reactions: serde_json::to_string(&message.reactions)?,
+            stickers: serde_json::to_string(&message.sticker_items)?,
+            raw: message.raw.as_ref().map(|raw| raw.to_string()),
+        })
+    }
+
+    pub fn for_messages(messages: &[Message]) -> Result<Vec<Self>> {
+        messages.iter().map(Self::new).collect()
+    }
+}
+
+// Every author of a page once, a row can't be upserted twice by the same statement.
+pub(crate) fn distinct_authors(messages: &[Message]) -> Vec<&User> {
+    let mut seen_author_ids = HashSet::new();
+    messages
+        .iter()
+        .filter_map(|message| message.author.as_ref())
+        .filter(|author| seen_author_ids.insert(author.id))
+        .collect()
+}
+
+// The attachments of a page along with the id of their message.
+pub(crate) fn message_attachments(messages: &[Message]) -> Vec<(u64, &Attachment)> {
+    messages
+        .iter()
+        .flat_map(|message| {
+            message
+                .attachments
+                .iter()
+                .map(move |attachment| (message.message_id, attachment))
+        })
+        .collect()
+}
+
+// Every rejected message of a page once. Messages without an id can't conflict, they're all kept.
+pub(crate) fn distinct_rejected_messages(
+    rejected_messages: &[RejectedMessage],
+) -> Vec<&RejectedMessage> {
+    let mut seen_message_ids = HashSet::new();
+    rejected_messages
+        .iter()
+        .filter(|rejected_message| match rejected_message.message_id {
+            Some(message_id) => seen_message_ids.insert((rejected_message.channel_id, message_id)),
+            None => true,
+        })
+        .collect()
+}
+
+// Every guild of the channels once, guilds only get a row of their id.
+pub(crate) fn distinct_guild_ids(channels: &[Channel]) -> Vec<u64> {
+    let mut seen_guild_ids = HashSet::new();
+    channels
+        .iter()
+        .filter_map(|channel| channel.guild_id)
+        .filter(|guild_id| seen_guild_ids.insert(*guild_id))
+        .collect()
+}
+
+// A table the SQL savers upsert into: its columns in the order their values are pushed, and the
+// key a conflicting row is matched on. The other columns are updated on conflict.
+pub(crate) struct UpsertTable {
+    name: &'static str,
+    columns: &'static [&'static str],
+    key: &'static [&'static str],
+}
+
+pub(crate) const MESSAGES_TABLE: UpsertTable = UpsertTable {
+    name: "messages",
+    columns: &[
+        "channel_id",
+        "parent_channel_id",
+        "author_id",
+        "message_id",
+        "message",
+        "has_media",
+        "author_name",
+        "timestamp",
+        "edited_timestamp",
+        "message_type",
+        "flags",
+        "reply_to_message_id",
+        "attachments",
+        "embeds",
+        "reactions",
+        "stickers",
+        "raw",
+    ],
+    key: &["message_id"],
+};
+
+pub(crate) const AUTHORS_TABLE: UpsertTable = UpsertTable {
+    name: "authors",
+    columns: &["author_id", "username", "global_name", "bot"],
+    key: &["author_id"],
+};
+
+pub(crate) const ATTACHMENTS_TABLE: UpsertTable = UpsertTable {
+    name: "attachments",
+    columns: &[
+        "attachment_id",
+        "message_id",
+        "filename",
+        "content_type",
+        "size",
+        "url",
+        "width",
+        "height",
+    ],
+    key: &["attachment_id"],
+};
+
+pub(crate) const CHANNELS_TABLE: UpsertTable = UpsertTable {
+    name: "channels",
+    columns: &[
+        "channel_id",
+        "guild_id",
+        "parent_channel_id",
+        "name",
+        "channel_type",
+    ],
+    key: &["channel_id"],
+};
+
+pub(crate) const REJECTED_MESSAGES_TABLE: UpsertTable = UpsertTable {
+    name: "rejected_messages",
+    columns: &["channel_id", "message_id", "reason", "raw"],
+    key: &["channel_id", "message_id"],
+};
+
+impl UpsertTable {
+    // `INSERT INTO <table> (<columns>) `, followed by the values.
+    pub fn insert_into(&self) -> String {
+        format!("INSERT INTO {} ({}) ", self.name, self.columns.join(", "))
+    }
+
+    // The conflict clause of MySQL, which matches rows on any unique key of the table.
+    pub fn on_duplicate_key_update(&self) -> String {
+        let assignments: Vec<String> = self
+            .updated_columns()
+            .map(|column| format!("{0} = VALUES({0})", column))
+            .collect();
+        format!(" ON DUPLICATE KEY UPDATE {}", assignments.join(", "))
+    }
+
+    // The conflict clause of PostgreSQL and SQLite.
+    pub fn on_conflict_do_update(&self) -> String {
+        let assignments: Vec<String> = self
+            .updated_columns()
+            .map(|column| format!("{0} = excluded.{0}", column))
+            .collect();
+        format!(
+            " ON CONFLICT ({}) DO UPDATE SET {}",
+            self.key.join(", "),
+            assignments.join(", ")
+        )
+    }
+
+    fn updated_columns(&self) -> impl Iterator<Item = &&'static str> {
+        self.columns
+            .iter()
+            .filter(|column| !self.key.contains(column))
+    }
+}
+
+// A row of the `messages` table joined with its author, as read back by the SQL savers. Every
+// backend casts its columns into these types: snowflakes as signed integers and JSON as text.
+#[derive(sqlx::FromRow)]
+pub(crate) struct MessageRow {
+    channel_id: i64,
+    parent_channel_id: Option<i64>,
+    author_id: i64,
+    message_id: i64,
+    message: String,
+    has_media: bool,
+    author_name: Option<String>,
+    global_name: Option<String>,
+    bot: Option<bool>,
+    timestamp: Option<DateTime<Utc>>,
+    edited_timestamp: Option<DateTime<Utc>>,
+    message_type: i64,
+    flags: i64,
+    reply_to_message_id: Option<i64>,
+    attachments: String,
+    embeds: String,
+    reactions: String,
+    stickers: String,
+    raw: Option<String>,
+}
+
+impl MessageRow {
+    // Messages saved in raw mode are parsed again from their API object, which also brings back
+    // what the columns don't hold such as mentions.
+    pub fn into_message(self) -> Result<Message> {
+        let channel_id = snowflake_from_i64(self.channel_id);
+        let parent_channel_id = self.parent_channel_id.map(snowflake_from_i64);
+        if let Some(raw) = &self.raw {
+            let raw_message: serde_json::Value = serde_json::from_str(raw)?;
+            let mut message = Message::from_raw(channel_id, &raw_message, true)?;
+            message.parent_channel_id = parent_channel_id;
+            return Ok(message);
+        }
+        let author_id = snowflake_from_i64(self.author_id);
+        Ok(Message {
+            channel_id,
+            parent_channel_id,
+            author_id,
+            message_id: snowflake_from_i64(self.message_id),
+            message: self.message,
+            has_media: self.has_media,
+            timestamp: self.timestamp,
+            edited_timestamp: self.edited_timestamp,
+            author: self.author_name.map(|username| User {
+                id: author_id,
+                username,
+                global_name: self.global_name,
+                discriminator: None,
+                avatar: None,
+                bot: self.bot.unwrap_or_default(),
+            }),
+            kind: u8::try_from(self.message_type).unwrap_or_default(),
+            flags: snowflake_from_i64(self.flags),
+            pinned: false,
+            tts: false,
+            mention_everyone: false,
+            mentions: Vec::new(),
+            mention_roles: Vec::new(),
+            attachments: serde_json::from_str(&self.attachments)?,
+            embeds: serde_json::from_str(&self.embeds)?,
+            reactions: serde_json::from_str(&self.reactions)?,
+            message_reference: self.reply_to_message_id.map(|reply_to_message_id| {
+                MessageReference {
+                    kind: 0,
+                    message_id: Some(snowflake_from_i64(reply_to_message_id)),
+                    channel_id: Some(channel_id),
+                    guild_id: None,
+                }
+            }),
+            sticker_items: serde_json::from_str(&self.stickers)?,
+            raw: None,
+        })
+    }
+}
+
+impl SqlSaver {
+    // The messages of a channel and of its threads, oldest first.
+    pub async fn channel_messages(&self, channel_id: u64) -> Result<Vec<Message>> {
+        let rows = sqlx::query_as::<_, MessageRow>(
+            "SELECT CAST(messages.channel_id AS SIGNED) AS channel_id, CAST(messages.parent_channel_id AS SIGNED) AS parent_channel_id, CAST(messages.author_id AS SIGNED) AS author_id, CAST(messages.message_id AS SIGNED) AS message_id, messages.message, messages.has_media, messages.author_name, authors.global_name, authors.bot, messages.timestamp, messages.edited_timestamp, CAST(messages.message_type AS SIGNED) AS message_type, CAST(messages.flags AS SIGNED) AS flags, CAST(messages.reply_to_message_id AS SIGNED) AS reply_to_message_id, CAST(messages.attachments AS CHAR) AS attachments, CAST(messages.embeds AS CHAR) AS embeds, CAST(messages.reactions AS CHAR) AS reactions, CAST(messages.stickers AS CHAR) AS stickers, CAST(messages.raw AS CHAR) AS raw FROM messages LEFT JOIN authors ON authors.author_id = messages.author_id WHERE messages.channel_id = ? OR messages.parent_channel_id = ? ORDER BY messages.message_id",
+        )
+        .bind(channel_id)
+        .bind(channel_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter().map(MessageRow::into_message).collect()
+    }
+
+    async fn upsert_authors(
+        transaction: &mut Transaction<'_, MySql>,
+        messages: &[Message],
+    ) -> Result<()> {
+        let authors = distinct_authors(messages);
+        if authors.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<MySql>::new(AUTHORS_TABLE.insert_into());
+        query_builder.push_values(authors, |mut row, author| {
+            row.push_bind(author.id)
+                .push_bind(author.username.clone())
+                .push_bind(author.global_name.clone())
+                .push_bind(author.bot);
+        });
+        query_builder.push(AUTHORS_TABLE.on_duplicate_key_update());
+        query_builder.build().execute(transaction).await?;
+        Ok(())
+    }
+
+    async fn upsert_attachments(
+        transaction: &mut Transaction<'_, MySql>,
+        messages: &[Message],
+    ) -> Result<()> {
+        let attachments = message_attachments(messages);
+        if attachments.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<MySql>::new(ATTACHMENTS_TABLE.insert_into());
+        query_builder.push_values(attachments, |mut row, (message_id, attachment)| {
+            row.push_bind(attachment.id)
+                .push_bind(message_id)
+                .push_bind(attachment.filename.clone())
+                .push_bind(attachment.content_type.clone())
+                .push_bind(attachment.size)
+                .push_bind(attachment.url.clone())
+                .push_bind(attachment.width)
+                .push_bind(attachment.height);
+        });
+        query_builder.push(ATTACHMENTS_TABLE.on_duplicate_key_update());
+        query_builder.build().execute(transaction).await?;
+        Ok(())
+    }
+
+    // Guild names aren't known, existing rows are left as they are.
+    async fn insert_guilds(
+        transaction: &mut Transaction<'_, MySql>,
+        channels: &[Channel],
+    ) -> Result<()> {
+        let guild_ids = distinct_guild_ids(channels);
+        if guild_ids.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<MySql>::new("INSERT INTO guilds (guild_id) ");
+        query_builder.push_values(guild_ids, |mut row, guild_id| {
+            row.push_bind(guild_id);
+        });
+        query_builder.push(" ON DUPLICATE KEY UPDATE guild_id = guild_id");
+        query_builder.build().execute(transaction).await?;
+        Ok(())
     }
 }
 
 #[async_trait]
 impl MessageSaver for SqlSaver {
+    // A page is saved in a single transaction. Messages saved before are updated, so scraping a
+    // channel again refreshes edited messages instead of failing on the primary key.
     async fn save_messages(&mut self, messages: &[Message]) -> Result<()> {
-        for message in messages {
-            sqlx::query(
-                "INSERT INTO messages (channel_id, author_id, message_id, message, has_media) VALUES (?, ?, ?, ?, ?)"
-            )
-            .bind(message.channel_id)
-            .bind(message.author_id)
-            .bind(message.message_id)
-            .bind(&message.message)
-            .bind(message.has_media)
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let json_columns = MessageJsonColumns::for_messages(messages)?;
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder = QueryBuilder::<MySql>::new(MESSAGES_TABLE.insert_into());
+        query_builder.push_values(
+            messages.iter().zip(json_columns),
+            |mut row, (message, json_columns)| {
+                row.push_bind(message.channel_id)
+                    .push_bind(message.parent_channel_id)
+                    .push_bind(message.author_id)
+                    .push_bind(message.message_id)
+                    .push_bind(message.message.clone())
+                    .push_bind(message.has_media)
+                    .push_bind(message.author.as_ref().map(|author| author.username.clone()))
+                    .push_bind(message.timestamp)
+                    .push_bind(message.edited_timestamp)
+                    .push_bind(message.kind)
+                    .push_bind(message.flags)
+                    .push_bind(message.reply_to_message_id())
+                    .push_bind(json_columns.attachments)
+                    .push_bind(json_columns.embeds)
+                    .push_bind(json_columns.reactions)
+                    .push_bind(json_columns.stickers)
+                    .push_bind(json_columns.raw);
+            },
+        );
+        query_builder.push(MESSAGES_TABLE.on_duplicate_key_update());
+        query_builder.build().execute(&mut transaction).await?;
+        Self::upsert_authors(&mut transaction, messages).await?;
+        Self::upsert_attachments(&mut transaction, messages).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    // Rejected messages are upserted on their channel and id, so a re-run doesn't duplicate them.
+    async fn save_rejected_messages(
+        &mut self,
+        rejected_messages: &[RejectedMessage],
+    ) -> Result<()> {
+        let rejected_messages = distinct_rejected_messages(rejected_messages);
+        if rejected_messages.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<MySql>::new(REJECTED_MESSAGES_TABLE.insert_into());
+        query_builder.push_values(rejected_messages, |mut row, rejected_message| {
+            row.push_bind(rejected_message.channel_id)
+                .push_bind(rejected_message.message_id)
+                .push_bind(rejected_message.reason.clone())
+                .push_bind(rejected_message.raw.to_string());
+        });
+        query_builder.push(REJECTED_MESSAGES_TABLE.on_duplicate_key_update());
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn load_checkpoint(&mut self, channel_id: u64) -> Result<Option<ScrapeCheckpoint>> {
+        let row = sqlx::query_as::<_, (Option<u64>, Option<u64>, bool)>(
+            "SELECT oldest_message_id, newest_message_id, completed FROM scrape_checkpoints WHERE channel_id = ?"
+        )
+        .bind(channel_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|(oldest_message_id, newest_message_id, completed)| ScrapeCheckpoint {
+            channel_id,
+            oldest_message_id,
+            newest_message_id,
+            completed,
+        }))
+    }
+
+    async fn save_checkpoint(&mut self, checkpoint: &ScrapeCheckpoint) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO scrape_checkpoints (channel_id, oldest_message_id, newest_message_id, completed) VALUES (?, ?, ?, ?) ON DUPLICATE KEY UPDATE oldest_message_id = VALUES(oldest_message_id), newest_message_id = VALUES(newest_message_id), completed = VALUES(completed)"
+        )
+        .bind(checkpoint.channel_id)
+        .bind(checkpoint.oldest_message_id)
+        .bind(checkpoint.newest_message_id)
+        .bind(checkpoint.completed)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn newest_archived_message_id(&mut self, channel_id: u64) -> Result<Option<u64>> {
+        let newest_message_id =
+            sqlx::query_scalar::<_, Option<u64>>("SELECT MAX(message_id) FROM messages WHERE channel_id = ?")
+                .bind(channel_id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(newest_message_id)
+    }
+
+    async fn save_channels(&mut self, channels: &[Channel]) -> Result<()> {
+        if channels.is_empty() {
+            return Ok(());
+        }
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder = QueryBuilder::<MySql>::new(CHANNELS_TABLE.insert_into());
+        query_builder.push_values(channels, |mut row, channel| {
+            row.push_bind(channel.id)
+                .push_bind(channel.guild_id)
+                .push_bind(channel.parent_id)
+                .push_bind(channel.name.clone())
+                .push_bind(channel.kind);
+        });
+        query_builder.push(CHANNELS_TABLE.on_duplicate_key_update());
+        query_builder.build().execute(&mut transaction).await?;
+        Self::insert_guilds(&mut transaction, channels).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn start_scrape_run(&mut self, channel_id: u64) -> Result<()> {
+        let result = sqlx::query("INSERT INTO scrape_runs (channel_id, started_at) VALUES (?, ?)")
+            .bind(channel_id)
+            .bind(Utc::now())
             .execute(&self.pool)
             .await?;
-        }
+        self.scrape_run_id = Some(result.last_insert_id());
+        Ok(())
+    }
+
+    async fn finish_scrape_run(&mut self, outcome: &ScrapeRunOutcome) -> Result<()> {
+        let Some(scrape_run_id) = self.scrape_run_id.take() else {
+            return Ok(());
+        };
+        sqlx::query(
+            "UPDATE scrape_runs SET finished_at = ?, saved_messages = ?, rejected_messages = ?, completed = ? WHERE id = ?",
+        )
+        .bind(Utc::now())
+        .bind(outcome.saved_messages)
+        .bind(outcome.rejected_messages)
+        .bind(outcome.completed)
+        .bind(scrape_run_id)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 }
\ No newline at end of file
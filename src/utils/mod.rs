@@ -1 +1,10 @@
-pub mod message_saver;
\ No newline at end of file
+pub mod channel_filter;
+pub mod checkpoint;
+pub mod compression;
+pub mod csv_saver;
+pub mod message_saver;
+pub mod parquet_saver;
+pub mod postgres_saver;
+pub mod snowflake;
+pub mod sql_schema;
+pub mod sqlite_saver;
\ No newline at end of file
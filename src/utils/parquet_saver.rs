@@ -0,0 +1,235 @@
+use crate::discord_api::{Message, RejectedMessage};
+use crate::utils::checkpoint::ScrapeCheckpoint;
+use crate::utils::message_saver::{
+    sibling_path, MessageJsonColumns, MessageSaver, RejectedMessagesFile,
+};
+use crate::utils::snowflake::snowflake_to_i64;
+use arrow_array::{
+    ArrayRef, BooleanArray, Int16Array, Int64Array, RecordBatch, StringArray,
+    TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 10_000;
+
+const UTC: &str = "UTC";
+
+// The columns of the SQL `messages` table, snowflakes as (signed) int64s like in the databases
+// without unsigned integers, timestamps in microseconds and the nested objects as JSON strings.
+pub fn messages_schema() -> SchemaRef {
+    let snowflake = |name: &str, nullable: bool| Field::new(name, DataType::Int64, nullable);
+    let timestamp = |name: &str| {
+        Field::new(
+            name,
+            DataType::Timestamp(TimeUnit::Microsecond, Some(UTC.into())),
+            true,
+        )
+    };
+    Arc::new(Schema::new(vec![
+        snowflake("channel_id", false),
+        snowflake("parent_channel_id", true),
+        snowflake("author_id", false),
+        snowflake("message_id", false),
+        Field::new("message", DataType::Utf8, false),
+        Field::new("has_media", DataType::Boolean, false),
+        Field::new("author_name", DataType::Utf8, true),
+        timestamp("timestamp"),
+        timestamp("edited_timestamp"),
+        Field::new("message_type", DataType::Int16, false),
+        Field::new("flags", DataType::Int64, false),
+        Field::new("pinned", DataType::Boolean, false),
+        snowflake("reply_to_message_id", true),
+        Field::new("attachments", DataType::Utf8, false),
+        Field::new("embeds", DataType::Utf8, false),
+        Field::new("reactions", DataType::Utf8, false),
+        Field::new("stickers", DataType::Utf8, false),
+        Field::new("raw", DataType::Utf8, true),
+    ]))
+}
+
+fn record_batch(schema: &SchemaRef, messages: &[Message]) -> Result<RecordBatch> {
+    let json_columns = MessageJsonColumns::for_messages(messages)?;
+    let snowflakes = |snowflake: fn(&Message) -> u64| -> ArrayRef {
+        Arc::new(Int64Array::from_iter_values(
+            messages
+                .iter()
+                .map(|message| snowflake_to_i64(snowflake(message))),
+        ))
+    };
+    let optional_snowflakes = |snowflake: fn(&Message) -> Option<u64>| -> ArrayRef {
+        Arc::new(Int64Array::from_iter(
+            messages
+                .iter()
+                .map(|message| snowflake(message).map(snowflake_to_i64)),
+        ))
+    };
+    let timestamps =
+        |timestamp: fn(&Message) -> Option<DateTime<Utc>>| -> ArrayRef {
+            Arc::new(
+                TimestampMicrosecondArray::from_iter(messages.iter().map(|message| {
+                    timestamp(message).map(|timestamp| timestamp.timestamp_micros())
+                }))
+                .with_timezone(UTC),
+            )
+        };
+    let json_strings = |json_column: fn(&MessageJsonColumns) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(
+            json_columns.iter().map(json_column),
+        ))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        snowflakes(|message| message.channel_id),
+        optional_snowflakes(|message| message.parent_channel_id),
+        snowflakes(|message| message.author_id),
+        snowflakes(|message| message.message_id),
+        Arc::new(StringArray::from_iter_values(
+            messages.iter().map(|message| &message.message),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            messages.iter().map(|message| Some(message.has_media)),
+        )),
+        Arc::new(StringArray::from_iter(messages.iter().map(|message| {
+            message.author.as_ref().map(|author| &author.username)
+        }))),
+        timestamps(|message| message.timestamp),
+        timestamps(|message| message.edited_timestamp),
+        Arc::new(Int16Array::from_iter_values(
+            messages.iter().map(|message| i16::from(message.kind)),
+        )),
+        snowflakes(|message| message.flags),
+        Arc::new(BooleanArray::from_iter(
+            messages.iter().map(|message| Some(message.pinned)),
+        )),
+        optional_snowflakes(Message::reply_to_message_id),
+        json_strings(|json_columns| &json_columns.attachments),
+        json_strings(|json_columns| &json_columns.embeds),
+        json_strings(|json_columns| &json_columns.reactions),
+        json_strings(|json_columns| &json_columns.stickers),
+        Arc::new(StringArray::from_iter(
+            json_columns
+                .iter()
+                .map(|json_columns| json_columns.raw.as_deref()),
+        )),
+    ];
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+// Writes the messages of a channel into a Parquet file, buffering them so that every row group
+// holds `row_group_size` messages. A Parquet file can't be appended to: the file is written anew
+// and only readable once `finish` wrote its footer. Rejected messages go to a JSONL file next to
+// it, as they don't fit the schema.
+pub struct ParquetSaver {
+    schema: SchemaRef,
+    // `None` once finished. The writer isn't `Sync`, the mutex makes the saver shareable while
+    // `&mut self` gets to the writer without locking.
+    writer: Option<Mutex<ArrowWriter<std::fs::File>>>,
+    pending_messages: Vec<Message>,
+    row_group_size: usize,
+    rejected_messages_file: RejectedMessagesFile,
+}
+
+impl ParquetSaver {
+    pub async fn new(path: &Path, row_group_size: usize) -> Result<Self> {
+        let row_group_size = row_group_size.max(1);
+        let schema = messages_schema();
+        let writer_properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_row_count(Some(row_group_size))
+            .build();
+        let file = tokio::fs::File::create(path).await?.into_std().await;
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(writer_properties))?;
+        Ok(Self {
+            schema,
+            writer: Some(Mutex::new(writer)),
+            pending_messages: Vec::new(),
+            row_group_size,
+            rejected_messages_file: RejectedMessagesFile::new(sibling_path(path, "rejected.jsonl")),
+        })
+    }
+
+    // Writes the pending messages as a row group of their own. Encoding and writing a row group
+    // blocks for a while, so it runs on a blocking thread rather than on the runtime.
+    async fn write_row_group(&mut self) -> Result<()> {
+        if self.pending_messages.is_empty() {
+            return Ok(());
+        }
+        let batch = record_batch(&self.schema, &self.pending_messages)?;
+        let Some(mut writer) = self.writer.take() else {
+            return Err(eyre!("The Parquet file is already finished"));
+        };
+        // The writer is handed back even when the write failed.
+        let (writer, written) = tokio::task::spawn_blocking(move || {
+            let written = writer
+                .get_mut()
+                .map_err(|_| eyre!("The Parquet writer is poisoned"))
+                .and_then(|writer| {
+                    writer.write(&batch)?;
+                    writer.flush()?;
+                    Ok(())
+                });
+            (writer, written)
+        })
+        .await?;
+        self.writer = Some(writer);
+        written?;
+        self.pending_messages.clear();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageSaver for ParquetSaver {
+    async fn save_messages(&mut self, messages: &[Message]) -> Result<()> {
+        for message in messages {
+            self.pending_messages.push(message.clone());
+            if self.pending_messages.len() >= self.row_group_size {
+                self.write_row_group().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn save_rejected_messages(
+        &mut self,
+        rejected_messages: &[RejectedMessage],
+    ) -> Result<()> {
+        self.rejected_messages_file.append(rejected_messages).await
+    }
+
+    // Every scrape writes a new file, there's nothing to resume from.
+    async fn load_checkpoint(&mut self, _channel_id: u64) -> Result<Option<ScrapeCheckpoint>> {
+        Ok(None)
+    }
+
+    async fn newest_archived_message_id(&mut self, _channel_id: u64) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    async fn save_checkpoint(&mut self, _checkpoint: &ScrapeCheckpoint) -> Result<()> {
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        self.write_row_group().await?;
+        if let Some(writer) = self.writer.take() {
+            // Closing writes the footer, on a blocking thread like the row groups.
+            tokio::task::spawn_blocking(move || -> Result<()> {
+                writer
+                    .into_inner()
+                    .map_err(|_| eyre!("The Parquet writer is poisoned"))?
+                    .close()?;
+                Ok(())
+            })
+            .await??;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+
+// Milliseconds between the Unix epoch and the first second of 2015, the epoch of Discord ids.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+const TIMESTAMP_SHIFT: u8 = 22;
+
+// The smallest snowflake that could have been created at the given time.
+pub fn snowflake_from_datetime(datetime: DateTime<Utc>) -> u64 {
+    let milliseconds_since_discord_epoch = (datetime.timestamp_millis() - DISCORD_EPOCH_MS).max(0);
+    (milliseconds_since_discord_epoch as u64) << TIMESTAMP_SHIFT
+}
+
+// When the object with the given snowflake was created, to the millisecond.
+pub fn datetime_from_snowflake(snowflake: u64) -> DateTime<Utc> {
+    let milliseconds_since_discord_epoch = (snowflake >> TIMESTAMP_SHIFT) as i64;
+    DateTime::from_timestamp_millis(DISCORD_EPOCH_MS + milliseconds_since_discord_epoch)
+        .unwrap_or_default()
+}
+
+// Databases without unsigned 64 bit integers store snowflakes as signed ones, which they fit in
+// until 2084.
+pub fn snowflake_to_i64(snowflake: u64) -> i64 {
+    snowflake as i64
+}
+
+pub fn snowflake_from_i64(snowflake: i64) -> u64 {
+    snowflake as u64
+}
\ No newline at end of file
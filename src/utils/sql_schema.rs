@@ -0,0 +1,565 @@
+use color_eyre::eyre::Result;
+use sqlx::database::HasArguments;
+use sqlx::{
+    Arguments, ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Pool, Row, Type,
+};
+
+// The SQL savers create and upgrade their own tables: every migration is applied once, in version
+// order, and recorded in `schema_migrations` so later versions of the scraper only apply what's
+// new. Migrations are never edited once released, changes go into a new one.
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    // Run one by one since not every backend takes several statements in a single query.
+    pub statements: &'static [&'static str],
+    // Added after the statements, skipping the ones the table already has. `ADD COLUMN IF NOT
+    // EXISTS` isn't available everywhere, the columns are looked up with the columns query of the
+    // backend instead.
+    pub added_columns: &'static [AddedColumn],
+}
+
+pub struct AddedColumn {
+    pub table: &'static str,
+    pub name: &'static str,
+    pub definition: &'static str,
+}
+
+const fn column(table: &'static str, name: &'static str, definition: &'static str) -> AddedColumn {
+    AddedColumn {
+        table,
+        name,
+        definition,
+    }
+}
+
+const CREATE_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    version BIGINT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (version)
+)";
+
+// `IF NOT EXISTS` lets the first migration adopt tables created by hand before migrations existed.
+pub const MYSQL_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "messages",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS messages (
+                channel_id BIGINT UNSIGNED NOT NULL,
+                parent_channel_id BIGINT UNSIGNED NULL,
+                author_id BIGINT UNSIGNED NOT NULL,
+                message_id BIGINT UNSIGNED NOT NULL,
+                message TEXT NOT NULL,
+                has_media BOOLEAN NOT NULL,
+                author_name VARCHAR(255) NULL,
+                timestamp DATETIME(6) NULL,
+                edited_timestamp DATETIME(6) NULL,
+                message_type TINYINT UNSIGNED NOT NULL,
+                flags BIGINT UNSIGNED NOT NULL,
+                reply_to_message_id BIGINT UNSIGNED NULL,
+                attachments JSON NOT NULL,
+                embeds JSON NOT NULL,
+                reactions JSON NOT NULL,
+                stickers JSON NOT NULL,
+                raw JSON NULL,
+                PRIMARY KEY (message_id),
+                INDEX messages_channel_id (channel_id)
+            )",
+            "CREATE TABLE IF NOT EXISTS rejected_messages (
+                id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+                channel_id BIGINT UNSIGNED NOT NULL,
+                message_id BIGINT UNSIGNED NULL,
+                reason TEXT NOT NULL,
+                raw JSON NOT NULL,
+                PRIMARY KEY (id)
+            )",
+            "CREATE TABLE IF NOT EXISTS scrape_checkpoints (
+                channel_id BIGINT UNSIGNED NOT NULL,
+                oldest_message_id BIGINT UNSIGNED NULL,
+                newest_message_id BIGINT UNSIGNED NULL,
+                completed BOOLEAN NOT NULL,
+                PRIMARY KEY (channel_id)
+            )",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 2,
+        name: "authors_attachments",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS authors (
+                author_id BIGINT UNSIGNED NOT NULL,
+                username VARCHAR(255) NOT NULL,
+                global_name VARCHAR(255) NULL,
+                bot BOOLEAN NOT NULL,
+                PRIMARY KEY (author_id)
+            )",
+            "CREATE TABLE IF NOT EXISTS attachments (
+                attachment_id BIGINT UNSIGNED NOT NULL,
+                message_id BIGINT UNSIGNED NOT NULL,
+                filename VARCHAR(1024) NOT NULL,
+                content_type VARCHAR(255) NULL,
+                size BIGINT UNSIGNED NOT NULL,
+                url TEXT NOT NULL,
+                width INT UNSIGNED NULL,
+                height INT UNSIGNED NULL,
+                PRIMARY KEY (attachment_id),
+                INDEX attachments_message_id (message_id)
+            )",
+        ],
+        added_columns: &[],
+    },
+    // `messages` tables created by hand from the first README only have its first five columns.
+    Migration {
+        version: 3,
+        name: "messages_details",
+        statements: &[],
+        added_columns: &[
+            column("messages", "parent_channel_id", "BIGINT UNSIGNED NULL"),
+            column("messages", "author_name", "VARCHAR(255) NULL"),
+            column("messages", "timestamp", "DATETIME(6) NULL"),
+            column("messages", "edited_timestamp", "DATETIME(6) NULL"),
+            column(
+                "messages",
+                "message_type",
+                "TINYINT UNSIGNED NOT NULL DEFAULT 0",
+            ),
+            column("messages", "flags", "BIGINT UNSIGNED NOT NULL DEFAULT 0"),
+            column("messages", "reply_to_message_id", "BIGINT UNSIGNED NULL"),
+            column(
+                "messages",
+                "attachments",
+                "JSON NOT NULL DEFAULT (JSON_ARRAY())",
+            ),
+            column("messages", "embeds", "JSON NOT NULL DEFAULT (JSON_ARRAY())"),
+            column(
+                "messages",
+                "reactions",
+                "JSON NOT NULL DEFAULT (JSON_ARRAY())",
+            ),
+            column(
+                "messages",
+                "stickers",
+                "JSON NOT NULL DEFAULT (JSON_ARRAY())",
+            ),
+            column("messages", "raw", "JSON NULL"),
+        ],
+    },
+    // Rejected messages were inserted again by every re-run, the duplicates are dropped before
+    // they get a unique key. MySQL has no `CREATE INDEX IF NOT EXISTS`, the index comes last.
+    Migration {
+        version: 4,
+        name: "rejected_messages_key",
+        statements: &[
+            "DELETE older FROM rejected_messages AS older
+                JOIN rejected_messages AS newer
+                ON newer.channel_id = older.channel_id
+                AND newer.message_id = older.message_id
+                AND newer.id > older.id",
+            "CREATE UNIQUE INDEX rejected_messages_channel_id_message_id
+                ON rejected_messages (channel_id, message_id)",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 5,
+        name: "guilds_channels_scrape_runs",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS guilds (
+                guild_id BIGINT UNSIGNED NOT NULL,
+                name VARCHAR(255) NULL,
+                PRIMARY KEY (guild_id)
+            )",
+            "CREATE TABLE IF NOT EXISTS channels (
+                channel_id BIGINT UNSIGNED NOT NULL,
+                guild_id BIGINT UNSIGNED NULL,
+                parent_channel_id BIGINT UNSIGNED NULL,
+                name VARCHAR(255) NULL,
+                channel_type TINYINT UNSIGNED NOT NULL,
+                PRIMARY KEY (channel_id),
+                INDEX channels_guild_id (guild_id)
+            )",
+            "CREATE TABLE IF NOT EXISTS scrape_runs (
+                id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+                channel_id BIGINT UNSIGNED NOT NULL,
+                started_at DATETIME(6) NOT NULL,
+                finished_at DATETIME(6) NULL,
+                saved_messages BIGINT UNSIGNED NOT NULL DEFAULT 0,
+                rejected_messages BIGINT UNSIGNED NOT NULL DEFAULT 0,
+                completed BOOLEAN NOT NULL DEFAULT FALSE,
+                PRIMARY KEY (id),
+                INDEX scrape_runs_channel_id (channel_id)
+            )",
+        ],
+        added_columns: &[],
+    },
+];
+
+// Lists the columns of a table, whose name is bound after the query.
+pub const MYSQL_COLUMNS_QUERY: &str =
+    "SELECT CAST(column_name AS CHAR) FROM information_schema.columns \
+    WHERE table_schema = DATABASE() AND table_name = ";
+
+// PostgreSQL has no unsigned integers, snowflakes are stored as signed BIGINTs.
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "messages",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS messages (
+                channel_id BIGINT NOT NULL,
+                parent_channel_id BIGINT NULL,
+                author_id BIGINT NOT NULL,
+                message_id BIGINT NOT NULL,
+                message TEXT NOT NULL,
+                has_media BOOLEAN NOT NULL,
+                author_name VARCHAR(255) NULL,
+                timestamp TIMESTAMPTZ NULL,
+                edited_timestamp TIMESTAMPTZ NULL,
+                message_type SMALLINT NOT NULL,
+                flags BIGINT NOT NULL,
+                reply_to_message_id BIGINT NULL,
+                attachments JSONB NOT NULL,
+                embeds JSONB NOT NULL,
+                reactions JSONB NOT NULL,
+                stickers JSONB NOT NULL,
+                raw JSONB NULL,
+                PRIMARY KEY (message_id)
+            )",
+            "CREATE INDEX IF NOT EXISTS messages_channel_id ON messages (channel_id)",
+            "CREATE TABLE IF NOT EXISTS rejected_messages (
+                id BIGSERIAL NOT NULL,
+                channel_id BIGINT NOT NULL,
+                message_id BIGINT NULL,
+                reason TEXT NOT NULL,
+                raw JSONB NOT NULL,
+                PRIMARY KEY (id)
+            )",
+            "CREATE TABLE IF NOT EXISTS scrape_checkpoints (
+                channel_id BIGINT NOT NULL,
+                oldest_message_id BIGINT NULL,
+                newest_message_id BIGINT NULL,
+                completed BOOLEAN NOT NULL,
+                PRIMARY KEY (channel_id)
+            )",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 2,
+        name: "authors_attachments",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS authors (
+                author_id BIGINT NOT NULL,
+                username VARCHAR(255) NOT NULL,
+                global_name VARCHAR(255) NULL,
+                bot BOOLEAN NOT NULL,
+                PRIMARY KEY (author_id)
+            )",
+            "CREATE TABLE IF NOT EXISTS attachments (
+                attachment_id BIGINT NOT NULL,
+                message_id BIGINT NOT NULL,
+                filename VARCHAR(1024) NOT NULL,
+                content_type VARCHAR(255) NULL,
+                size BIGINT NOT NULL,
+                url TEXT NOT NULL,
+                width INTEGER NULL,
+                height INTEGER NULL,
+                PRIMARY KEY (attachment_id)
+            )",
+            "CREATE INDEX IF NOT EXISTS attachments_message_id ON attachments (message_id)",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 3,
+        name: "messages_details",
+        statements: &[],
+        added_columns: &[
+            column("messages", "parent_channel_id", "BIGINT NULL"),
+            column("messages", "author_name", "VARCHAR(255) NULL"),
+            column("messages", "timestamp", "TIMESTAMPTZ NULL"),
+            column("messages", "edited_timestamp", "TIMESTAMPTZ NULL"),
+            column("messages", "message_type", "SMALLINT NOT NULL DEFAULT 0"),
+            column("messages", "flags", "BIGINT NOT NULL DEFAULT 0"),
+            column("messages", "reply_to_message_id", "BIGINT NULL"),
+            column("messages", "attachments", "JSONB NOT NULL DEFAULT '[]'"),
+            column("messages", "embeds", "JSONB NOT NULL DEFAULT '[]'"),
+            column("messages", "reactions", "JSONB NOT NULL DEFAULT '[]'"),
+            column("messages", "stickers", "JSONB NOT NULL DEFAULT '[]'"),
+            column("messages", "raw", "JSONB NULL"),
+        ],
+    },
+    Migration {
+        version: 4,
+        name: "rejected_messages_key",
+        statements: &[
+            "DELETE FROM rejected_messages
+                WHERE message_id IS NOT NULL
+                AND id NOT IN (
+                    SELECT MAX(id) FROM rejected_messages GROUP BY channel_id, message_id
+                )",
+            "CREATE UNIQUE INDEX IF NOT EXISTS rejected_messages_channel_id_message_id
+                ON rejected_messages (channel_id, message_id)",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 5,
+        name: "guilds_channels_scrape_runs",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS guilds (
+                guild_id BIGINT NOT NULL,
+                name VARCHAR(255) NULL,
+                PRIMARY KEY (guild_id)
+            )",
+            "CREATE TABLE IF NOT EXISTS channels (
+                channel_id BIGINT NOT NULL,
+                guild_id BIGINT NULL,
+                parent_channel_id BIGINT NULL,
+                name VARCHAR(255) NULL,
+                channel_type SMALLINT NOT NULL,
+                PRIMARY KEY (channel_id)
+            )",
+            "CREATE INDEX IF NOT EXISTS channels_guild_id ON channels (guild_id)",
+            "CREATE TABLE IF NOT EXISTS scrape_runs (
+                id BIGSERIAL NOT NULL,
+                channel_id BIGINT NOT NULL,
+                started_at TIMESTAMPTZ NOT NULL,
+                finished_at TIMESTAMPTZ NULL,
+                saved_messages BIGINT NOT NULL DEFAULT 0,
+                rejected_messages BIGINT NOT NULL DEFAULT 0,
+                completed BOOLEAN NOT NULL DEFAULT FALSE,
+                PRIMARY KEY (id)
+            )",
+            "CREATE INDEX IF NOT EXISTS scrape_runs_channel_id ON scrape_runs (channel_id)",
+        ],
+        added_columns: &[],
+    },
+];
+
+pub const POSTGRES_COLUMNS_QUERY: &str =
+    "SELECT column_name::TEXT FROM information_schema.columns \
+    WHERE table_schema = current_schema() AND table_name = ";
+
+// SQLite stores snowflakes as signed integers and JSON as text.
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "messages",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS messages (
+                channel_id INTEGER NOT NULL,
+                parent_channel_id INTEGER NULL,
+                author_id INTEGER NOT NULL,
+                message_id INTEGER NOT NULL PRIMARY KEY,
+                message TEXT NOT NULL,
+                has_media BOOLEAN NOT NULL,
+                author_name TEXT NULL,
+                timestamp DATETIME NULL,
+                edited_timestamp DATETIME NULL,
+                message_type INTEGER NOT NULL,
+                flags INTEGER NOT NULL,
+                reply_to_message_id INTEGER NULL,
+                attachments TEXT NOT NULL,
+                embeds TEXT NOT NULL,
+                reactions TEXT NOT NULL,
+                stickers TEXT NOT NULL,
+                raw TEXT NULL
+            )",
+            "CREATE INDEX IF NOT EXISTS messages_channel_id ON messages (channel_id)",
+            "CREATE TABLE IF NOT EXISTS rejected_messages (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                channel_id INTEGER NOT NULL,
+                message_id INTEGER NULL,
+                reason TEXT NOT NULL,
+                raw TEXT NOT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS scrape_checkpoints (
+                channel_id INTEGER NOT NULL PRIMARY KEY,
+                oldest_message_id INTEGER NULL,
+                newest_message_id INTEGER NULL,
+                completed BOOLEAN NOT NULL
+            )",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 2,
+        name: "authors_attachments",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS authors (
+                author_id INTEGER NOT NULL PRIMARY KEY,
+                username TEXT NOT NULL,
+                global_name TEXT NULL,
+                bot BOOLEAN NOT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS attachments (
+                attachment_id INTEGER NOT NULL PRIMARY KEY,
+                message_id INTEGER NOT NULL,
+                filename TEXT NOT NULL,
+                content_type TEXT NULL,
+                size INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                width INTEGER NULL,
+                height INTEGER NULL
+            )",
+            "CREATE INDEX IF NOT EXISTS attachments_message_id ON attachments (message_id)",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 3,
+        name: "messages_details",
+        statements: &[],
+        added_columns: &[
+            column("messages", "parent_channel_id", "INTEGER NULL"),
+            column("messages", "author_name", "TEXT NULL"),
+            column("messages", "timestamp", "DATETIME NULL"),
+            column("messages", "edited_timestamp", "DATETIME NULL"),
+            column("messages", "message_type", "INTEGER NOT NULL DEFAULT 0"),
+            column("messages", "flags", "INTEGER NOT NULL DEFAULT 0"),
+            column("messages", "reply_to_message_id", "INTEGER NULL"),
+            column("messages", "attachments", "TEXT NOT NULL DEFAULT '[]'"),
+            column("messages", "embeds", "TEXT NOT NULL DEFAULT '[]'"),
+            column("messages", "reactions", "TEXT NOT NULL DEFAULT '[]'"),
+            column("messages", "stickers", "TEXT NOT NULL DEFAULT '[]'"),
+            column("messages", "raw", "TEXT NULL"),
+        ],
+    },
+    Migration {
+        version: 4,
+        name: "rejected_messages_key",
+        statements: &[
+            "DELETE FROM rejected_messages
+                WHERE message_id IS NOT NULL
+                AND id NOT IN (
+                    SELECT MAX(id) FROM rejected_messages GROUP BY channel_id, message_id
+                )",
+            "CREATE UNIQUE INDEX IF NOT EXISTS rejected_messages_channel_id_message_id
+                ON rejected_messages (channel_id, message_id)",
+        ],
+        added_columns: &[],
+    },
+    Migration {
+        version: 5,
+        name: "guilds_channels_scrape_runs",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS guilds (
+                guild_id INTEGER NOT NULL PRIMARY KEY,
+                name TEXT NULL
+            )",
+            "CREATE TABLE IF NOT EXISTS channels (
+                channel_id INTEGER NOT NULL PRIMARY KEY,
+                guild_id INTEGER NULL,
+                parent_channel_id INTEGER NULL,
+                name TEXT NULL,
+                channel_type INTEGER NOT NULL
+            )",
+            "CREATE INDEX IF NOT EXISTS channels_guild_id ON channels (guild_id)",
+            "CREATE TABLE IF NOT EXISTS scrape_runs (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                channel_id INTEGER NOT NULL,
+                started_at DATETIME NOT NULL,
+                finished_at DATETIME NULL,
+                saved_messages INTEGER NOT NULL DEFAULT 0,
+                rejected_messages INTEGER NOT NULL DEFAULT 0,
+                completed BOOLEAN NOT NULL DEFAULT FALSE
+            )",
+            "CREATE INDEX IF NOT EXISTS scrape_runs_channel_id ON scrape_runs (channel_id)",
+        ],
+        added_columns: &[],
+    },
+];
+
+pub const SQLITE_COLUMNS_QUERY: &str = "SELECT columns.name FROM sqlite_master \
+    JOIN pragma_table_info(sqlite_master.name) AS columns \
+    WHERE sqlite_master.type = 'table' AND sqlite_master.name = ";
+
+// Applies the migrations missing from the database, returns the versions it applied.
+// `columns_query` is the `*_COLUMNS_QUERY` of the backend.
+pub async fn migrate<DB>(
+    pool: &Pool<DB>,
+    migrations: &[Migration],
+    columns_query: &str,
+) -> Result<Vec<i64>>
+where
+    DB: Database,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB> + Default,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    i64: Type<DB> + for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB>,
+    String: Type<DB> + for<'r> Decode<'r, DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    sqlx::query(CREATE_MIGRATIONS_TABLE).execute(pool).await?;
+    let applied_versions: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_migrations")
+        .fetch_all(pool)
+        .await?;
+    let mut newly_applied_versions = Vec::new();
+    for migration in migrations {
+        if applied_versions.contains(&migration.version) {
+            continue;
+        }
+        tracing::info!(
+            "Migrating the database to version {} ({}).",
+            migration.version,
+            migration.name
+        );
+        // MySQL commits DDL statements implicitly, elsewhere a failed migration is rolled back.
+        let mut transaction = pool.begin().await?;
+        for statement in migration.statements {
+            sqlx::query(statement).execute(&mut *transaction).await?;
+        }
+        for added_column in migration.added_columns {
+            let mut columns_query = columns_query.to_string();
+            let mut arguments = <DB as HasArguments>::Arguments::default();
+            push_bind::<DB, _>(&mut columns_query, &mut arguments, added_column.table)?;
+            let columns: Vec<String> = sqlx::query_with(&columns_query, arguments)
+                .fetch_all(&mut *transaction)
+                .await?
+                .iter()
+                .map(|row| row.try_get(0))
+                .collect::<Result<_, _>>()?;
+            if columns.iter().any(|name| name == added_column.name) {
+                continue;
+            }
+            // Table and column names are constants of the scraper, nothing to escape.
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                added_column.table, added_column.name, added_column.definition
+            ))
+            .execute(&mut *transaction)
+            .await?;
+        }
+        let mut insert_migration =
+            String::from("INSERT INTO schema_migrations (version, name) VALUES (");
+        let mut arguments = <DB as HasArguments>::Arguments::default();
+        push_bind::<DB, _>(&mut insert_migration, &mut arguments, migration.version)?;
+        insert_migration.push_str(", ");
+        push_bind::<DB, _>(&mut insert_migration, &mut arguments, migration.name)?;
+        insert_migration.push(')');
+        sqlx::query_with(&insert_migration, arguments)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        newly_applied_versions.push(migration.version);
+    }
+    Ok(newly_applied_versions)
+}
+
+// Binds a value and appends its placeholder to the query, `?` or `$n` depending on the backend.
+fn push_bind<'q, DB, T>(
+    query: &mut String,
+    arguments: &mut <DB as HasArguments<'q>>::Arguments,
+    value: T,
+) -> std::fmt::Result
+where
+    DB: Database,
+    T: 'q + Send + Encode<'q, DB> + Type<DB>,
+{
+    arguments.add(value);
+    arguments.format_placeholder(query)
+}
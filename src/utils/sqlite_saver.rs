@@ -0,0 +1,276 @@
+use crate::discord_api::models::Channel;
+use crate::discord_api::{Message, RejectedMessage};
+use crate::utils::checkpoint::ScrapeCheckpoint;
+use crate::utils::message_saver::{
+    distinct_authors, distinct_guild_ids, distinct_rejected_messages, message_attachments,
+    MessageJsonColumns, MessageRow, MessageSaver, ScrapeRunOutcome, ATTACHMENTS_TABLE,
+    AUTHORS_TABLE, CHANNELS_TABLE, MESSAGES_TABLE, REJECTED_MESSAGES_TABLE,
+};
+use crate::utils::snowflake::{snowflake_from_i64, snowflake_to_i64};
+use crate::utils::sql_schema::{self, SQLITE_COLUMNS_QUERY, SQLITE_MIGRATIONS};
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::eyre::Result;
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqlitePool};
+use sqlx::{QueryBuilder, Transaction};
+use std::path::Path;
+
+// Cloning shares the connection pool.
+#[derive(Clone)]
+pub struct SqliteSaver {
+    pool: SqlitePool,
+    // The `scrape_runs` row of the channel being scraped.
+    scrape_run_id: Option<i64>,
+}
+
+impl SqliteSaver {
+    // Creates the database file and its tables when they don't exist yet.
+    pub async fn new(path: &Path) -> Result<Self> {
+        let connect_options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(connect_options.clone()).await?;
+        sql_schema::migrate(&pool, SQLITE_MIGRATIONS, SQLITE_COLUMNS_QUERY).await?;
+        // Connections of the pool can keep the schema they read before the migrations, which
+        // SQLite matches `ON CONFLICT` targets against. The saver starts over with new ones.
+        pool.close().await;
+        let pool = SqlitePool::connect_with(connect_options).await?;
+        Ok(Self {
+            pool,
+            scrape_run_id: None,
+        })
+    }
+
+    // The messages of a channel and of its threads, oldest first.
+    pub async fn channel_messages(&self, channel_id: u64) -> Result<Vec<Message>> {
+        let rows = sqlx::query_as::<_, MessageRow>(
+            "SELECT messages.channel_id, messages.parent_channel_id, messages.author_id, messages.message_id, messages.message, messages.has_media, messages.author_name, authors.global_name, authors.bot, messages.timestamp, messages.edited_timestamp, messages.message_type, messages.flags, messages.reply_to_message_id, messages.attachments, messages.embeds, messages.reactions, messages.stickers, messages.raw FROM messages LEFT JOIN authors ON authors.author_id = messages.author_id WHERE messages.channel_id = ?1 OR messages.parent_channel_id = ?1 ORDER BY messages.message_id",
+        )
+        .bind(snowflake_to_i64(channel_id))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter().map(MessageRow::into_message).collect()
+    }
+}
+
+impl SqliteSaver {
+    async fn upsert_authors(
+        transaction: &mut Transaction<'_, Sqlite>,
+        messages: &[Message],
+    ) -> Result<()> {
+        let authors = distinct_authors(messages);
+        if authors.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<Sqlite>::new(AUTHORS_TABLE.insert_into());
+        query_builder.push_values(authors, |mut row, author| {
+            row.push_bind(snowflake_to_i64(author.id))
+                .push_bind(author.username.clone())
+                .push_bind(author.global_name.clone())
+                .push_bind(author.bot);
+        });
+        query_builder.push(AUTHORS_TABLE.on_conflict_do_update());
+        query_builder.build().execute(transaction).await?;
+        Ok(())
+    }
+
+    async fn upsert_attachments(
+        transaction: &mut Transaction<'_, Sqlite>,
+        messages: &[Message],
+    ) -> Result<()> {
+        let attachments = message_attachments(messages);
+        if attachments.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<Sqlite>::new(ATTACHMENTS_TABLE.insert_into());
+        query_builder.push_values(attachments, |mut row, (message_id, attachment)| {
+            row.push_bind(snowflake_to_i64(attachment.id))
+                .push_bind(snowflake_to_i64(message_id))
+                .push_bind(attachment.filename.clone())
+                .push_bind(attachment.content_type.clone())
+                .push_bind(attachment.size as i64)
+                .push_bind(attachment.url.clone())
+                .push_bind(attachment.width)
+                .push_bind(attachment.height);
+        });
+        query_builder.push(ATTACHMENTS_TABLE.on_conflict_do_update());
+        query_builder.build().execute(transaction).await?;
+        Ok(())
+    }
+
+    // Guild names aren't known, existing rows are left as they are.
+    async fn insert_guilds(
+        transaction: &mut Transaction<'_, Sqlite>,
+        channels: &[Channel],
+    ) -> Result<()> {
+        let guild_ids = distinct_guild_ids(channels);
+        if guild_ids.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<Sqlite>::new("INSERT INTO guilds (guild_id) ");
+        query_builder.push_values(guild_ids, |mut row, guild_id| {
+            row.push_bind(snowflake_to_i64(guild_id));
+        });
+        query_builder.push(" ON CONFLICT (guild_id) DO NOTHING");
+        query_builder.build().execute(transaction).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageSaver for SqliteSaver {
+    // One multi-row upsert per page, in a single transaction along with its authors and
+    // attachments.
+    async fn save_messages(&mut self, messages: &[Message]) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let json_columns = MessageJsonColumns::for_messages(messages)?;
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder = QueryBuilder::<Sqlite>::new(MESSAGES_TABLE.insert_into());
+        query_builder.push_values(
+            messages.iter().zip(json_columns),
+            |mut row, (message, json_columns)| {
+                row.push_bind(snowflake_to_i64(message.channel_id))
+                    .push_bind(message.parent_channel_id.map(snowflake_to_i64))
+                    .push_bind(snowflake_to_i64(message.author_id))
+                    .push_bind(snowflake_to_i64(message.message_id))
+                    .push_bind(message.message.clone())
+                    .push_bind(message.has_media)
+                    .push_bind(
+                        message
+                            .author
+                            .as_ref()
+                            .map(|author| author.username.clone()),
+                    )
+                    .push_bind(message.timestamp)
+                    .push_bind(message.edited_timestamp)
+                    .push_bind(message.kind)
+                    .push_bind(snowflake_to_i64(message.flags))
+                    .push_bind(message.reply_to_message_id().map(snowflake_to_i64))
+                    .push_bind(json_columns.attachments)
+                    .push_bind(json_columns.embeds)
+                    .push_bind(json_columns.reactions)
+                    .push_bind(json_columns.stickers)
+                    .push_bind(json_columns.raw);
+            },
+        );
+        query_builder.push(MESSAGES_TABLE.on_conflict_do_update());
+        query_builder.build().execute(&mut transaction).await?;
+        Self::upsert_authors(&mut transaction, messages).await?;
+        Self::upsert_attachments(&mut transaction, messages).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    // Rejected messages are upserted on their channel and id, so a re-run doesn't duplicate them.
+    async fn save_rejected_messages(
+        &mut self,
+        rejected_messages: &[RejectedMessage],
+    ) -> Result<()> {
+        let rejected_messages = distinct_rejected_messages(rejected_messages);
+        if rejected_messages.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder = QueryBuilder::<Sqlite>::new(REJECTED_MESSAGES_TABLE.insert_into());
+        query_builder.push_values(rejected_messages, |mut row, rejected_message| {
+            row.push_bind(snowflake_to_i64(rejected_message.channel_id))
+                .push_bind(rejected_message.message_id.map(snowflake_to_i64))
+                .push_bind(rejected_message.reason.clone())
+                .push_bind(rejected_message.raw.to_string());
+        });
+        query_builder.push(REJECTED_MESSAGES_TABLE.on_conflict_do_update());
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn load_checkpoint(&mut self, channel_id: u64) -> Result<Option<ScrapeCheckpoint>> {
+        let row = sqlx::query_as::<_, (Option<i64>, Option<i64>, bool)>(
+            "SELECT oldest_message_id, newest_message_id, completed FROM scrape_checkpoints WHERE channel_id = ?",
+        )
+        .bind(snowflake_to_i64(channel_id))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(
+            |(oldest_message_id, newest_message_id, completed)| ScrapeCheckpoint {
+                channel_id,
+                oldest_message_id: oldest_message_id.map(snowflake_from_i64),
+                newest_message_id: newest_message_id.map(snowflake_from_i64),
+                completed,
+            },
+        ))
+    }
+
+    async fn save_checkpoint(&mut self, checkpoint: &ScrapeCheckpoint) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO scrape_checkpoints (channel_id, oldest_message_id, newest_message_id, completed) VALUES (?, ?, ?, ?) ON CONFLICT (channel_id) DO UPDATE SET oldest_message_id = excluded.oldest_message_id, newest_message_id = excluded.newest_message_id, completed = excluded.completed",
+        )
+        .bind(snowflake_to_i64(checkpoint.channel_id))
+        .bind(checkpoint.oldest_message_id.map(snowflake_to_i64))
+        .bind(checkpoint.newest_message_id.map(snowflake_to_i64))
+        .bind(checkpoint.completed)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn newest_archived_message_id(&mut self, channel_id: u64) -> Result<Option<u64>> {
+        let newest_message_id = sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT MAX(message_id) FROM messages WHERE channel_id = ?",
+        )
+        .bind(snowflake_to_i64(channel_id))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(newest_message_id.map(snowflake_from_i64))
+    }
+
+    async fn save_channels(&mut self, channels: &[Channel]) -> Result<()> {
+        if channels.is_empty() {
+            return Ok(());
+        }
+        let mut transaction = self.pool.begin().await?;
+        let mut query_builder = QueryBuilder::<Sqlite>::new(CHANNELS_TABLE.insert_into());
+        query_builder.push_values(channels, |mut row, channel| {
+            row.push_bind(snowflake_to_i64(channel.id))
+                .push_bind(channel.guild_id.map(snowflake_to_i64))
+                .push_bind(channel.parent_id.map(snowflake_to_i64))
+                .push_bind(channel.name.clone())
+                .push_bind(channel.kind);
+        });
+        query_builder.push(CHANNELS_TABLE.on_conflict_do_update());
+        query_builder.build().execute(&mut transaction).await?;
+        Self::insert_guilds(&mut transaction, channels).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn start_scrape_run(&mut self, channel_id: u64) -> Result<()> {
+        let scrape_run_id = sqlx::query(
+            "INSERT INTO scrape_runs (channel_id, started_at) VALUES (?, ?)",
+        )
+        .bind(snowflake_to_i64(channel_id))
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        self.scrape_run_id = Some(scrape_run_id);
+        Ok(())
+    }
+
+    async fn finish_scrape_run(&mut self, outcome: &ScrapeRunOutcome) -> Result<()> {
+        let Some(scrape_run_id) = self.scrape_run_id.take() else {
+            return Ok(());
+        };
+        sqlx::query(
+            "UPDATE scrape_runs SET finished_at = ?, saved_messages = ?, rejected_messages = ?, completed = ? WHERE id = ?",
+        )
+        .bind(Utc::now())
+        .bind(outcome.saved_messages as i64)
+        .bind(outcome.rejected_messages as i64)
+        .bind(outcome.completed)
+        .bind(scrape_run_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use discord_rust_scraper::discord_api::cassette::{Interaction, ReplayTransport};
+use discord_rust_scraper::discord_api::FakeTransport;
+use discord_rust_scraper::{DiscordApi, Message, Scraper};
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+fn cassette_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cassettes")
+        .join(name)
+}
+
+async fn replay_messages(cassette_path: &Path, channel_id: u64) -> Vec<Message> {
+    let discord_api = DiscordApi::builder("token", false)
+        .transport(ReplayTransport::from_file(cassette_path).await.unwrap())
+        .build()
+        .unwrap();
+    Scraper::new(discord_api)
+        .messages(channel_id)
+        .map(|message| message.unwrap())
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn recorded_release_notes_keep_their_details() {
+    let messages = replay_messages(&cassette_path("release_notes.jsonl"), 659069446438125570).await;
+
+    assert_eq!(messages.len(), 2);
+    let release_notes = &messages[0];
+    assert_eq!(release_notes.message_id, 1235526416413966397);
+    assert_eq!(release_notes.kind, 19);
+    assert!(release_notes.pinned);
+    assert!(release_notes.edited_timestamp.is_some());
+    assert_eq!(
+        release_notes.reply_to_message_id(),
+        Some(1235526100012310578)
+    );
+    assert_eq!(release_notes.embeds[0].title.as_deref(), Some("v1.0.8"));
+    assert_eq!(release_notes.embeds[0].fields[0].name, "Breaking");
+    assert_eq!(release_notes.reactions[0].count, 3);
+    assert_eq!(release_notes.reactions[0].emoji.name.as_deref(), Some("🎉"));
+
+    let changelog = &messages[1];
+    assert!(changelog.has_media);
+    assert_eq!(changelog.attachments[0].filename, "changelog.png");
+    assert_eq!(changelog.attachments[0].width, Some(1280));
+    assert_eq!(changelog.sticker_items[0].name, "Wave");
+    assert_eq!(
+        changelog.author.as_ref().unwrap().global_name.as_deref(),
+        Some("Ferris")
+    );
+}
+
+#[tokio::test]
+async fn recording_scrubs_credentials_and_replays_the_same_messages() {
+    let transport = FakeTransport::default();
+    transport.respond_json(
+        "channels/1/messages?limit=100",
+        StatusCode::OK,
+        &json!([{
+            "id": "2",
+            "author": { "id": "3", "username": "ferris" },
+            "content": "recorded",
+        }]),
+    );
+    transport.respond_json(
+        "channels/1/messages?before=2&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let cassette_path = std::env::temp_dir().join(format!(
+        "discord_rust_scraper_cassette_{}.jsonl",
+        std::process::id()
+    ));
+    let discord_api = DiscordApi::builder("secret-token", false)
+        .transport(transport)
+        .record_to(&cassette_path)
+        .build()
+        .unwrap();
+    let recorded_messages: Vec<Message> = Scraper::new(discord_api)
+        .messages(1)
+        .map(|message| message.unwrap())
+        .collect()
+        .await;
+
+    let cassette = std::fs::read_to_string(&cassette_path).unwrap();
+    assert!(!cassette.contains("secret-token"));
+    let interactions: Vec<Interaction> = cassette
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(interactions.len(), 2);
+    assert_eq!(
+        interactions[0].request.url,
+        "/api/v9/channels/1/messages?limit=100"
+    );
+    assert_eq!(
+        interactions[0].request.headers["authorization"],
+        "[scrubbed]"
+    );
+
+    let replayed_messages = replay_messages(&cassette_path, 1).await;
+    std::fs::remove_file(&cassette_path).unwrap();
+    assert_eq!(replayed_messages.len(), recorded_messages.len());
+    assert_eq!(replayed_messages[0].message, "recorded");
+}
@@ -0,0 +1,42 @@
+// Fixtures shared by the integration tests, every test file only uses some of them.
+#![allow(dead_code)]
+
+use discord_rust_scraper::Message;
+use serde_json::json;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+// The files of a test, in a directory of their own that is removed along with them once dropped,
+// even when the test fails half way.
+pub struct TempFiles {
+    dir: TempDir,
+}
+
+pub fn temp_files() -> TempFiles {
+    TempFiles {
+        dir: tempfile::Builder::new()
+            .prefix("discord_rust_scraper_")
+            .tempdir()
+            .unwrap(),
+    }
+}
+
+impl TempFiles {
+    // `general.jsonl` -> `<temp dir>/general.jsonl`, the file itself isn't created.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+}
+
+pub fn message(message_id: u64, content: &str) -> Message {
+    serde_json::from_value(json!({
+        "channel_id": 1,
+        "author_id": 3,
+        "message_id": message_id,
+        "message": content,
+        "has_media": false,
+        "timestamp": "2024-05-02T10:15:30.125000+00:00",
+        "author": { "id": "3", "username": "ferris" },
+    }))
+    .unwrap()
+}
@@ -0,0 +1,125 @@
+use common::{message, temp_files};
+use discord_rust_scraper::scraper::{convert_jsonl_file_into_csv, convert_jsonl_file_into_json};
+use discord_rust_scraper::utils::checkpoint::ScrapeCheckpoint;
+use discord_rust_scraper::utils::csv_saver::{CsvColumn, CsvOptions};
+use discord_rust_scraper::utils::message_saver::JsonlSaver;
+use discord_rust_scraper::{MessageSaver, ParseMode};
+use serde_json::Value;
+
+mod common;
+
+#[tokio::test]
+async fn gzip_archive_is_appended_to_and_read_back() {
+    let temp_files = temp_files();
+    let jsonl_path = temp_files.path("appended.jsonl.gz");
+
+    let mut saver = JsonlSaver::new(jsonl_path.to_str().unwrap()).await.unwrap();
+    saver
+        .save_messages(&[message(1235526100012310578, "first")])
+        .await
+        .unwrap();
+    // A resumed scrape appends to the archive with a saver of its own.
+    let mut saver = JsonlSaver::new(jsonl_path.to_str().unwrap()).await.unwrap();
+    saver
+        .save_messages(&[
+            message(1235526416413966397, "second"),
+            message(1235530000000000000, "third"),
+        ])
+        .await
+        .unwrap();
+    let newest_message_id = saver.newest_archived_message_id(1).await.unwrap();
+    let json_path = convert_jsonl_file_into_json(&jsonl_path, ParseMode::Strict)
+        .await
+        .unwrap();
+
+    let archive = std::fs::read(&jsonl_path).unwrap();
+    let json = std::fs::read_to_string(&json_path).unwrap();
+    assert_eq!(archive[..2], [0x1f, 0x8b]);
+    assert_eq!(newest_message_id, Some(1235530000000000000));
+    assert_eq!(json_path, temp_files.path("appended.json"));
+    let messages: Vec<Value> = serde_json::from_str(&json).unwrap();
+    let contents: Vec<&str> = messages
+        .iter()
+        .map(|message| message["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents, vec!["first", "second", "third"]);
+}
+
+#[tokio::test]
+async fn zstd_archive_is_converted_into_csv() {
+    let temp_files = temp_files();
+    let jsonl_path = temp_files.path("converted.jsonl.zst");
+
+    let mut saver = JsonlSaver::new(jsonl_path.to_str().unwrap()).await.unwrap();
+    saver
+        .save_messages(&[
+            message(1235526100012310578, "first"),
+            message(1235526416413966397, "second"),
+        ])
+        .await
+        .unwrap();
+    let csv_options = CsvOptions {
+        columns: vec![CsvColumn::MessageId, CsvColumn::Message],
+        ..CsvOptions::default()
+    };
+    let csv_path = convert_jsonl_file_into_csv(&jsonl_path, ParseMode::Strict, &csv_options)
+        .await
+        .unwrap();
+
+    let archive = std::fs::read(&jsonl_path).unwrap();
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    assert_eq!(archive[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+    assert_eq!(csv_path, temp_files.path("converted.csv"));
+    assert_eq!(
+        csv,
+        "message_id,message\r\n\
+         1235526100012310578,first\r\n\
+         1235526416413966397,second\r\n"
+    );
+}
+
+#[tokio::test]
+async fn compressed_archive_keeps_a_checkpoint_of_its_own() {
+    let temp_files = temp_files();
+    let plain_path = temp_files.path("checkpointed.jsonl");
+    let gzip_path = temp_files.path("checkpointed.jsonl.gz");
+
+    let mut gzip_saver = JsonlSaver::new(gzip_path.to_str().unwrap()).await.unwrap();
+    gzip_saver
+        .save_checkpoint(&ScrapeCheckpoint {
+            completed: true,
+            ..ScrapeCheckpoint::new(1)
+        })
+        .await
+        .unwrap();
+    let mut plain_saver = JsonlSaver::new(plain_path.to_str().unwrap()).await.unwrap();
+    let plain_checkpoint = plain_saver.load_checkpoint(1).await.unwrap();
+    let gzip_checkpoint = gzip_saver.load_checkpoint(1).await.unwrap();
+
+    assert!(temp_files.path("checkpointed.jsonl.gz.checkpoint.json").exists());
+    assert!(plain_checkpoint.is_none());
+    assert!(gzip_checkpoint.unwrap().completed);
+}
+
+#[tokio::test]
+async fn checkpoint_saved_under_its_previous_name_is_carried_over() {
+    let temp_files = temp_files();
+    let checkpoint = ScrapeCheckpoint {
+        oldest_message_id: Some(10),
+        ..ScrapeCheckpoint::new(1)
+    };
+    std::fs::write(
+        temp_files.path("earlier.gz.checkpoint.json"),
+        serde_json::to_string(&[checkpoint]).unwrap(),
+    )
+    .unwrap();
+
+    let mut saver = JsonlSaver::new(temp_files.path("earlier.jsonl.gz").to_str().unwrap())
+        .await
+        .unwrap();
+    let loaded_checkpoint = saver.load_checkpoint(1).await.unwrap().unwrap();
+
+    assert_eq!(loaded_checkpoint.oldest_message_id, Some(10));
+    assert!(temp_files.path("earlier.jsonl.gz.checkpoint.json").exists());
+    assert!(!temp_files.path("earlier.gz.checkpoint.json").exists());
+}
@@ -0,0 +1,135 @@
+use discord_rust_scraper::scraper::convert_jsonl_file_into_csv;
+use discord_rust_scraper::utils::csv_saver::{CsvColumn, CsvOptions};
+use discord_rust_scraper::{Message, ParseMode};
+use serde_json::json;
+use std::path::PathBuf;
+
+fn jsonl_archive(name: &str) -> PathBuf {
+    let jsonl_path = std::env::temp_dir().join(format!(
+        "discord_rust_scraper_{}_{}.jsonl",
+        std::process::id(),
+        name
+    ));
+    let messages = [
+        json!({
+            "channel_id": 1,
+            "author_id": 3,
+            "message_id": 1235526416413966397_u64,
+            "message": "first line, with a comma\nand a \"quoted\" second line",
+            "has_media": true,
+            "author": { "id": "3", "username": "ferris" },
+            "attachments": [
+                { "id": "5", "filename": "a.png", "size": 1, "url": "https://cdn/a.png" },
+                { "id": "6", "filename": "b.png", "size": 1, "url": "https://cdn/b.png" }
+            ],
+        }),
+        json!({
+            "channel_id": 1,
+            "author_id": 4,
+            "message_id": 1235526100012310578_u64,
+            "message": "tab\tseparated",
+            "has_media": false,
+        }),
+    ];
+    let lines: Vec<String> = messages.iter().map(|message| message.to_string()).collect();
+    std::fs::write(&jsonl_path, lines.join("\n")).unwrap();
+    jsonl_path
+}
+
+#[tokio::test]
+async fn convert_jsonl_archive_into_csv() {
+    let jsonl_path = jsonl_archive("csv");
+
+    let csv_path =
+        convert_jsonl_file_into_csv(&jsonl_path, ParseMode::Strict, &CsvOptions::default())
+            .await
+            .unwrap();
+
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    std::fs::remove_file(&jsonl_path).unwrap();
+    std::fs::remove_file(&csv_path).unwrap();
+    assert_eq!(csv_path, jsonl_path.with_extension("csv"));
+    assert_eq!(
+        csv,
+        "message_id,timestamp,author_id,author_name,message,attachments\r\n\
+         1235526416413966397,2024-05-02 09:40:52,3,ferris,\"first line, with a comma\n\
+         and a \"\"quoted\"\" second line\",https://cdn/a.png https://cdn/b.png\r\n\
+         1235526100012310578,2024-05-02 09:39:37,4,,tab\tseparated,\r\n"
+    );
+}
+
+#[tokio::test]
+async fn convert_jsonl_archive_into_tsv_with_selected_columns() {
+    let jsonl_path = jsonl_archive("tsv");
+    let tsv_options = CsvOptions {
+        columns: vec![CsvColumn::AuthorId, CsvColumn::Message, CsvColumn::HasMedia],
+        header: false,
+        ..CsvOptions::tsv()
+    };
+
+    let tsv_path = convert_jsonl_file_into_csv(&jsonl_path, ParseMode::Strict, &tsv_options)
+        .await
+        .unwrap();
+
+    let tsv = std::fs::read_to_string(&tsv_path).unwrap();
+    std::fs::remove_file(&jsonl_path).unwrap();
+    std::fs::remove_file(&tsv_path).unwrap();
+    assert_eq!(tsv_path, jsonl_path.with_extension("tsv"));
+    assert_eq!(
+        tsv,
+        "3\t\"first line, with a comma\nand a \"\"quoted\"\" second line\"\ttrue\r\n\
+         4\t\"tab\tseparated\"\tfalse\r\n"
+    );
+}
+
+#[test]
+fn cells_that_look_like_formulas_are_escaped_unless_disabled() {
+    let message: Message = serde_json::from_value(json!({
+        "channel_id": 1,
+        "author_id": 3,
+        "message_id": 1235526416413966397_u64,
+        "message": "=HYPERLINK(\"https://example.com\", \"click\")",
+        "has_media": false,
+        "author": { "id": "3", "username": "@ferris" },
+    }))
+    .unwrap();
+    let csv_options = CsvOptions {
+        columns: vec![
+            CsvColumn::MessageId,
+            CsvColumn::AuthorName,
+            CsvColumn::Message,
+        ],
+        ..CsvOptions::default()
+    };
+
+    let escaped_line = csv_options.message_line(&message);
+    let raw_line = CsvOptions {
+        escape_formulas: false,
+        ..csv_options.clone()
+    }
+    .message_line(&message);
+
+    assert_eq!(
+        escaped_line,
+        "1235526416413966397,'@ferris,\"'=HYPERLINK(\"\"https://example.com\"\", \"\"click\"\")\"\r\n"
+    );
+    assert_eq!(
+        raw_line,
+        "1235526416413966397,@ferris,\"=HYPERLINK(\"\"https://example.com\"\", \"\"click\"\")\"\r\n"
+    );
+
+    // Spreadsheets skip leading tabs and carriage returns before evaluating a formula.
+    let message: Message = serde_json::from_value(json!({
+        "channel_id": 1,
+        "author_id": 3,
+        "message_id": 1,
+        "message": "\r=1+1",
+        "has_media": false,
+        "author": { "id": "3", "username": "\t=1+1" },
+    }))
+    .unwrap();
+    assert_eq!(
+        csv_options.message_line(&message),
+        "1,'\t=1+1,\"'\r=1+1\"\r\n"
+    );
+}
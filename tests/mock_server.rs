@@ -0,0 +1,223 @@
+use discord_rust_scraper::discord_api::retry::RetryPolicy;
+use discord_rust_scraper::mock_server::{MockDiscordServer, MockServerConfig};
+use discord_rust_scraper::{DiscordApi, Scraper};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mock_discord")
+}
+
+// Ids of the messages of a JSONL archive, sorted.
+fn archived_message_ids(path: &Path) -> Vec<u64> {
+    let mut message_ids: Vec<u64> = std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<Value>(line).unwrap()["message_id"]
+                .as_u64()
+                .unwrap()
+        })
+        .collect();
+    message_ids.sort_unstable();
+    message_ids
+}
+
+fn message_ids(messages: &Value) -> Vec<u64> {
+    messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["id"].as_str().unwrap().parse().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn mock_server_paginates_messages() {
+    let server = MockDiscordServer::start(MockServerConfig::new(fixtures_dir()))
+        .await
+        .unwrap();
+    let get = |query: &'static str| {
+        let url = format!("{}/v9/channels/100/messages?{}", server.base_url(), query);
+        async move {
+            reqwest::get(url)
+                .await
+                .unwrap()
+                .json::<Value>()
+                .await
+                .unwrap()
+        }
+    };
+
+    assert_eq!(message_ids(&get("limit=2").await), vec![5, 4]);
+    assert_eq!(message_ids(&get("before=4&limit=2").await), vec![3, 2]);
+    assert_eq!(message_ids(&get("after=1&limit=2").await), vec![3, 2]);
+    assert_eq!(message_ids(&get("around=3&limit=3").await), vec![4, 3, 2]);
+
+    let response = reqwest::get(format!("{}/v9/channels/404/messages", server.base_url()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn scraper_pages_through_rate_limits_and_outages() {
+    let server = MockDiscordServer::start(MockServerConfig {
+        max_page_size: 2,
+        rate_limit: Some(1),
+        rate_limit_reset_after: Duration::from_millis(20),
+        too_many_requests_every: Some(3),
+        service_unavailable_every: Some(5),
+        ..MockServerConfig::new(fixtures_dir())
+    })
+    .await
+    .unwrap();
+    let discord_api = DiscordApi::builder("token", false)
+        .base_url(server.base_url())
+        .retry_policy(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        })
+        .build()
+        .unwrap();
+    let scraper = Scraper::new(discord_api);
+
+    let message_ids: Vec<u64> = scraper
+        .messages(100)
+        .map(|message| message.unwrap().message_id)
+        .collect()
+        .await;
+
+    assert_eq!(message_ids, vec![5, 4, 3, 2, 1]);
+}
+
+fn working_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "discord_rust_scraper_mock_server_{}_{}",
+        std::process::id(),
+        name
+    ))
+}
+
+// Runs the `scrape` command against the mock server in a working directory of its own, and
+// returns that directory.
+async fn run_scrape_command(name: &str, args: &[&str]) -> PathBuf {
+    let server = MockDiscordServer::start(MockServerConfig {
+        max_page_size: 2,
+        service_unavailable_every: Some(4),
+        ..MockServerConfig::new(fixtures_dir())
+    })
+    .await
+    .unwrap();
+    let working_dir = working_dir(name);
+    std::fs::create_dir_all(&working_dir).unwrap();
+    let base_url = server.base_url();
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+
+    let output = tokio::task::spawn_blocking(move || {
+        Command::new(env!("CARGO_BIN_EXE_discord_rust_scraper"))
+            .current_dir(&working_dir)
+            .args([
+                "scrape",
+                "--bot_token",
+                "token",
+                "--api_base_url",
+                &base_url,
+            ])
+            .args(["--initial_backoff_ms", "1", "--max_backoff_ms", "1"])
+            .args(args)
+            .output()
+            .map(|output| (output, working_dir))
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    let (output, working_dir) = output;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    working_dir
+}
+
+#[tokio::test]
+async fn scrape_command_archives_a_guild_from_the_mock_server() {
+    let working_dir = run_scrape_command("guild", &["--guild_id", "1", "--include_threads"]).await;
+
+    // Both channels are named `general`, each has an archive of its own.
+    let archived_message_ids =
+        |file_name: &str| archived_message_ids(&working_dir.join("storage").join(file_name));
+    let general_message_ids = archived_message_ids("general-100.jsonl");
+    let other_general_message_ids = archived_message_ids("general-200.jsonl");
+    std::fs::remove_dir_all(&working_dir).unwrap();
+    assert_eq!(general_message_ids, vec![1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(other_general_message_ids, vec![8]);
+}
+
+#[tokio::test]
+async fn scrape_command_scrapes_a_channel_listed_twice_once() {
+    let working_dir = run_scrape_command(
+        "listed_twice",
+        &[
+            "--guild_id",
+            "1",
+            "--channel_ids",
+            "100",
+            "200",
+            "--concurrency",
+            "3",
+        ],
+    )
+    .await;
+
+    let general_message_ids = archived_message_ids(&working_dir.join("storage/general-100.jsonl"));
+    let other_general_message_ids =
+        archived_message_ids(&working_dir.join("storage/general-200.jsonl"));
+    std::fs::remove_dir_all(&working_dir).unwrap();
+    assert_eq!(general_message_ids, vec![1, 2, 3, 4, 5]);
+    assert_eq!(other_general_message_ids, vec![8]);
+}
+
+#[tokio::test]
+async fn scrape_command_carries_on_with_archives_of_earlier_file_names() {
+    let storage_dir = working_dir("earlier_names").join("storage");
+    std::fs::create_dir_all(&storage_dir).unwrap();
+    let archived_message = |channel_id: u64, message_id: u64| {
+        let message = json!({
+            "channel_id": channel_id,
+            "author_id": 1,
+            "message_id": message_id,
+            "message": "archived",
+            "has_media": false,
+        });
+        format!("{}\n", message)
+    };
+    // Channel 100 was renamed since, channel 200 was archived before files had channel ids.
+    let renamed_archive: String = (1..=5)
+        .map(|message_id| archived_message(100, message_id))
+        .collect();
+    std::fs::write(storage_dir.join("lobby-100.jsonl"), renamed_archive).unwrap();
+    std::fs::write(storage_dir.join("general.jsonl"), archived_message(200, 8)).unwrap();
+
+    let working_dir = run_scrape_command(
+        "earlier_names",
+        &["--channel_ids", "100", "200", "--incremental"],
+    )
+    .await;
+
+    let storage_dir = working_dir.join("storage");
+    let general_message_ids = archived_message_ids(&storage_dir.join("general-100.jsonl"));
+    let other_general_message_ids = archived_message_ids(&storage_dir.join("general-200.jsonl"));
+    let earlier_archives_left = ["lobby-100.jsonl", "general.jsonl"]
+        .iter()
+        .any(|file_name| storage_dir.join(file_name).exists());
+    std::fs::remove_dir_all(&working_dir).unwrap();
+    assert_eq!(general_message_ids, vec![1, 2, 3, 4, 5]);
+    assert_eq!(other_general_message_ids, vec![8]);
+    assert!(!earlier_archives_left);
+}
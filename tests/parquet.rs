@@ -0,0 +1,106 @@
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Int64Type, TimestampMicrosecondType};
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, TimeUnit};
+use common::{message, temp_files};
+use discord_rust_scraper::scraper::convert_jsonl_file_into_parquet;
+use discord_rust_scraper::utils::parquet_saver::ParquetSaver;
+use discord_rust_scraper::{MessageSaver, ParseMode};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::path::Path;
+
+mod common;
+
+// The row group sizes along with all the rows.
+fn read_parquet(path: &Path) -> (Vec<i64>, RecordBatch) {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path).unwrap())
+        .unwrap()
+        .with_batch_size(1024);
+    let row_group_sizes = builder
+        .metadata()
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows())
+        .collect();
+    let mut batches: Vec<RecordBatch> = builder.build().unwrap().map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 1);
+    (row_group_sizes, batches.remove(0))
+}
+
+#[tokio::test]
+async fn parquet_saver_flushes_a_row_group_every_n_messages() {
+    let temp_files = temp_files();
+    let parquet_path = temp_files.path("row_groups.parquet");
+    let mut saver = ParquetSaver::new(&parquet_path, 2).await.unwrap();
+    saver
+        .save_messages(&[message(30, "c"), message(20, "b")])
+        .await
+        .unwrap();
+    saver.save_messages(&[message(10, "a")]).await.unwrap();
+
+    saver.finish().await.unwrap();
+
+    let (row_group_sizes, batch) = read_parquet(&parquet_path);
+    assert_eq!(row_group_sizes, vec![2, 1]);
+    let message_ids: Vec<i64> = batch
+        .column_by_name("message_id")
+        .unwrap()
+        .as_primitive::<Int64Type>()
+        .values()
+        .to_vec();
+    assert_eq!(message_ids, vec![30, 20, 10]);
+    let timestamps = batch.column_by_name("timestamp").unwrap();
+    assert_eq!(
+        timestamps.data_type(),
+        &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+    );
+    assert_eq!(
+        timestamps
+            .as_primitive::<TimestampMicrosecondType>()
+            .value(0),
+        1714644930125000
+    );
+    assert_eq!(
+        batch
+            .column_by_name("message")
+            .unwrap()
+            .as_string::<i32>()
+            .value(2),
+        "a"
+    );
+}
+
+#[tokio::test]
+async fn convert_jsonl_archive_into_parquet() {
+    let temp_files = temp_files();
+    let jsonl_path = temp_files.path("archive.jsonl");
+    let lines = [
+        serde_json::to_string(&message(20, "hello")).unwrap(),
+        "not json".to_string(),
+        serde_json::to_string(&message(10, "world")).unwrap(),
+    ];
+    std::fs::write(&jsonl_path, lines.join("\n")).unwrap();
+
+    let strict_result = convert_jsonl_file_into_parquet(&jsonl_path, ParseMode::Strict, 100).await;
+    let parquet_path = convert_jsonl_file_into_parquet(&jsonl_path, ParseMode::Lenient, 100)
+        .await
+        .unwrap();
+
+    assert!(strict_result.is_err());
+    assert_eq!(parquet_path, jsonl_path.with_extension("parquet"));
+    let (row_group_sizes, batch) = read_parquet(&parquet_path);
+    assert_eq!(row_group_sizes, vec![2]);
+    let author_names = batch
+        .column_by_name("author_name")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(author_names.value(0), "ferris");
+    assert_eq!(
+        batch
+            .column_by_name("message")
+            .unwrap()
+            .as_string::<i32>()
+            .value(1),
+        "world"
+    );
+}
@@ -0,0 +1,573 @@
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
+use discord_rust_scraper::discord_api::retry::RetryPolicy;
+use discord_rust_scraper::discord_api::{DiscordApiError, FakeTransport, RejectedMessage};
+use discord_rust_scraper::utils::checkpoint::ScrapeCheckpoint;
+use discord_rust_scraper::{
+    DiscordApi, Message, MessageSaver, ParseMode, ScrapeBounds, Scraper, ScraperError,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing_test::traced_test;
+
+#[derive(Default)]
+struct MemorySaver {
+    messages: Vec<Message>,
+    rejected_messages: Vec<RejectedMessage>,
+    checkpoint: Option<ScrapeCheckpoint>,
+    // Number of upcoming `save_messages` calls that fail.
+    failing_saves: u32,
+}
+
+#[async_trait]
+impl MessageSaver for MemorySaver {
+    async fn save_messages(&mut self, messages: &[Message]) -> Result<()> {
+        if self.failing_saves > 0 {
+            self.failing_saves -= 1;
+            return Err(eyre!("the disk is full"));
+        }
+        self.messages.extend_from_slice(messages);
+        Ok(())
+    }
+
+    async fn save_rejected_messages(
+        &mut self,
+        rejected_messages: &[RejectedMessage],
+    ) -> Result<()> {
+        self.rejected_messages.extend_from_slice(rejected_messages);
+        Ok(())
+    }
+
+    async fn load_checkpoint(&mut self, channel_id: u64) -> Result<Option<ScrapeCheckpoint>> {
+        Ok(self
+            .checkpoint
+            .clone()
+            .filter(|checkpoint| checkpoint.channel_id == channel_id))
+    }
+
+    async fn newest_archived_message_id(&mut self, _channel_id: u64) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    async fn save_checkpoint(&mut self, checkpoint: &ScrapeCheckpoint) -> Result<()> {
+        self.checkpoint = Some(checkpoint.clone());
+        Ok(())
+    }
+}
+
+fn scraper(transport: &Arc<FakeTransport>) -> Scraper {
+    let discord_api = DiscordApi::builder("token", false)
+        .transport(Arc::clone(transport))
+        .retry_policy(RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            ..RetryPolicy::default()
+        })
+        .build()
+        .unwrap();
+    Scraper::new(discord_api)
+}
+
+fn message(message_id: u64, content: &str) -> Value {
+    json!({
+        "id": message_id.to_string(),
+        "author": { "id": "80351110224678912", "username": "ferris" },
+        "content": content,
+        "timestamp": "2024-01-01T00:00:00+00:00",
+    })
+}
+
+fn text_channel(transport: &FakeTransport, channel_id: u64, last_message_id: u64) {
+    transport.respond_json(
+        &format!("channels/{}", channel_id),
+        StatusCode::OK,
+        &json!({
+            "id": channel_id.to_string(),
+            "type": 0,
+            "name": "general",
+            "last_message_id": last_message_id.to_string(),
+        }),
+    );
+}
+
+fn saved_message_ids(saver: &MemorySaver) -> Vec<u64> {
+    saver
+        .messages
+        .iter()
+        .map(|message| message.message_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn scrape_channel_pages_backwards_until_an_empty_page() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 10, 3);
+    transport.respond_json(
+        "channels/10/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(3, "third"), message(2, "second")]),
+    );
+    transport.respond_json(
+        "channels/10/messages?before=2&limit=100",
+        StatusCode::OK,
+        &json!([message(1, "first")]),
+    );
+    transport.respond_json(
+        "channels/10/messages?before=1&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver::default();
+
+    let summary = scraper(&transport)
+        .scrape_channel_with_saver(10, &mut saver)
+        .await
+        .unwrap();
+
+    assert_eq!(summary.channel_name, "general");
+    assert_eq!(summary.saved_messages, 3);
+    assert_eq!(saved_message_ids(&saver), vec![3, 2, 1]);
+    assert_eq!(saver.messages[2].message, "first");
+    assert_eq!(
+        transport.requested_urls(),
+        vec![
+            "channels/10",
+            "channels/10/messages?limit=100",
+            "channels/10/messages?before=2&limit=100",
+            "channels/10/messages?before=1&limit=100",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn scrape_channel_marks_the_checkpoint_completed_once_at_the_first_message() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 12, 2);
+    transport.respond_json(
+        "channels/12/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(2, "second")]),
+    );
+    transport.respond_json(
+        "channels/12/messages?before=2&limit=100",
+        StatusCode::OK,
+        &json!([message(1, "first")]),
+    );
+    transport.respond_json(
+        "channels/12/messages?before=1&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver::default();
+
+    scraper(&transport)
+        .scrape_channel_with_saver(12, &mut saver)
+        .await
+        .unwrap();
+
+    let checkpoint = saver.checkpoint.unwrap();
+    assert!(checkpoint.completed);
+    assert_eq!(checkpoint.oldest_message_id, Some(1));
+}
+
+#[tokio::test]
+async fn bounded_scrape_leaves_the_older_history_to_a_resume() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 17, 3);
+    transport.respond_json(
+        "channels/17/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(3, "third"), message(2, "second")]),
+    );
+    transport.respond_json(
+        "channels/17/messages?before=2&limit=100",
+        StatusCode::OK,
+        &json!([message(1, "first")]),
+    );
+    transport.respond_json(
+        "channels/17/messages?before=1&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver::default();
+
+    scraper(&transport)
+        .with_bounds(ScrapeBounds::new(None, None, Some(1), None))
+        .scrape_channel_with_saver(17, &mut saver)
+        .await
+        .unwrap();
+    let checkpoint = saver.checkpoint.clone().unwrap();
+    scraper(&transport)
+        .with_resume(true)
+        .scrape_channel_with_saver(17, &mut saver)
+        .await
+        .unwrap();
+
+    assert!(!checkpoint.completed);
+    assert_eq!(checkpoint.oldest_message_id, Some(2));
+    assert_eq!(saved_message_ids(&saver), vec![3, 2, 1]);
+    assert!(saver.checkpoint.unwrap().completed);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn scrape_channel_keeps_the_checkpoint_before_a_page_that_failed_to_save() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 15, 3);
+    transport.respond_json(
+        "channels/15/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(3, "third")]),
+    );
+    transport.respond_json(
+        "channels/15/messages?before=3&limit=100",
+        StatusCode::OK,
+        &json!([message(2, "second")]),
+    );
+    transport.respond_json(
+        "channels/15/messages?before=2&limit=100",
+        StatusCode::OK,
+        &json!([message(1, "first")]),
+    );
+    transport.respond_json(
+        "channels/15/messages?before=1&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver {
+        failing_saves: 1,
+        ..MemorySaver::default()
+    };
+
+    scraper(&transport)
+        .scrape_channel_with_saver(15, &mut saver)
+        .await
+        .unwrap();
+
+    // The first page is lost, so a resume has to start over from the newest message.
+    assert_eq!(saved_message_ids(&saver), vec![2, 1]);
+    assert!(saver.checkpoint.is_none());
+    assert!(logs_contain("failed to save, resume the scrape"));
+}
+
+#[tokio::test]
+async fn active_threads_are_listed_once_per_guild() {
+    let transport = Arc::new(FakeTransport::default());
+    for channel_id in [60, 61] {
+        let channel = json!({
+            "id": channel_id.to_string(),
+            "type": 0,
+            "guild_id": "6",
+            "name": "general",
+            "last_message_id": channel_id.to_string(),
+        });
+        transport.respond_json(
+            &format!("channels/{}", channel_id),
+            StatusCode::OK,
+            &channel,
+        );
+        transport.respond_json(
+            &format!("channels/{}/messages?limit=100", channel_id),
+            StatusCode::OK,
+            &json!([]),
+        );
+        for visibility in ["public", "private"] {
+            transport.respond_json(
+                &format!(
+                    "channels/{}/threads/archived/{}?limit=100",
+                    channel_id, visibility
+                ),
+                StatusCode::OK,
+                &json!({ "threads": [] }),
+            );
+        }
+    }
+    transport.respond_json(
+        "guilds/6/threads/active",
+        StatusCode::OK,
+        &json!({ "threads": [] }),
+    );
+    let scraper = scraper(&transport).with_include_threads(true);
+
+    for channel_id in [60, 61] {
+        scraper
+            .scrape_channel_with_saver(channel_id, &mut MemorySaver::default())
+            .await
+            .unwrap();
+    }
+
+    let active_thread_requests = transport
+        .requested_urls()
+        .iter()
+        .filter(|url| *url == "guilds/6/threads/active")
+        .count();
+    assert_eq!(active_thread_requests, 1);
+}
+
+#[tokio::test]
+async fn threads_are_not_listed_for_voice_channels() {
+    let transport = Arc::new(FakeTransport::default());
+    transport.respond_json(
+        "channels/70",
+        StatusCode::OK,
+        &json!({ "id": "70", "type": 2, "guild_id": "6", "name": "lounge" }),
+    );
+    transport.respond_json(
+        "channels/70/messages?limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver::default();
+
+    let summary = scraper(&transport)
+        .with_include_threads(true)
+        .scrape_channel_with_saver(70, &mut saver)
+        .await
+        .unwrap();
+
+    assert_eq!(summary.scraped_threads, 0);
+    assert_eq!(
+        transport.requested_urls(),
+        vec!["channels/70", "channels/70/messages?limit=100"]
+    );
+}
+
+#[tokio::test]
+async fn scrape_channel_names_dm_channels_after_their_id() {
+    let transport = Arc::new(FakeTransport::default());
+    transport.respond_json(
+        "channels/20",
+        StatusCode::OK,
+        &json!({ "id": "20", "type": 1, "last_message_id": "5" }),
+    );
+    transport.respond_json(
+        "channels/20/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(5, "hi")]),
+    );
+    transport.respond_json(
+        "channels/20/messages?before=5&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver::default();
+
+    let summary = scraper(&transport)
+        .scrape_channel_with_saver(20, &mut saver)
+        .await
+        .unwrap();
+
+    assert_eq!(summary.channel_name, "dm_20");
+    assert_eq!(saved_message_ids(&saver), vec![5]);
+}
+
+#[tokio::test]
+async fn scrape_channel_scrapes_a_channel_without_messages() {
+    let transport = Arc::new(FakeTransport::default());
+    transport.respond_json(
+        "channels/25",
+        StatusCode::OK,
+        &json!({ "id": "25", "type": 0, "name": "empty", "last_message_id": null }),
+    );
+    transport.respond_json("channels/25/messages?limit=100", StatusCode::OK, &json!([]));
+    let mut saver = MemorySaver::default();
+
+    let summary = scraper(&transport)
+        .scrape_channel_with_saver(25, &mut saver)
+        .await
+        .unwrap();
+
+    assert_eq!(summary.channel_name, "empty");
+    assert_eq!(summary.saved_messages, 0);
+}
+
+#[tokio::test]
+async fn scrape_channel_reports_missing_access() {
+    let transport = Arc::new(FakeTransport::default());
+    transport.respond_json(
+        "channels/30",
+        StatusCode::FORBIDDEN,
+        &json!({ "message": "Missing Access", "code": 50001 }),
+    );
+
+    let error = scraper(&transport)
+        .scrape_channel_with_saver(30, &mut MemorySaver::default())
+        .await
+        .unwrap_err();
+
+    assert!(error.is_missing_access());
+}
+
+#[tokio::test]
+async fn scrape_channel_retries_server_errors() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 40, 1);
+    transport.respond_json(
+        "channels/40/messages?limit=100",
+        StatusCode::SERVICE_UNAVAILABLE,
+        &json!({ "message": "Service Unavailable" }),
+    );
+    transport.respond_json(
+        "channels/40/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(1, "made it")]),
+    );
+    transport.respond_json(
+        "channels/40/messages?before=1&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver::default();
+
+    scraper(&transport)
+        .scrape_channel_with_saver(40, &mut saver)
+        .await
+        .unwrap();
+
+    assert_eq!(saved_message_ids(&saver), vec![1]);
+}
+
+#[tokio::test]
+async fn scrape_channel_gives_up_after_the_last_attempt() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 50, 1);
+    transport.respond_json(
+        "channels/50/messages?limit=100",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        &json!({ "message": "Internal Server Error" }),
+    );
+
+    let error = scraper(&transport)
+        .scrape_channel_with_saver(50, &mut MemorySaver::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        error,
+        ScraperError::DiscordApiError(DiscordApiError::RetriesExhausted(3, _))
+    ));
+}
+
+#[tokio::test]
+async fn scrape_channel_waits_out_rate_limits_whatever_the_retry_policy() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 55, 1);
+    for _ in 0..2 {
+        transport.respond_json(
+            "channels/55/messages?limit=100",
+            StatusCode::TOO_MANY_REQUESTS,
+            &json!({ "message": "You are being rate limited.", "retry_after": 0.0 }),
+        );
+    }
+    transport.respond_json(
+        "channels/55/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(1, "made it")]),
+    );
+    transport.respond_json(
+        "channels/55/messages?before=1&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    // A single attempt, and 429 isn't among the retryable status codes.
+    let discord_api = DiscordApi::builder("token", false)
+        .transport(Arc::clone(&transport))
+        .retry_policy(RetryPolicy {
+            max_attempts: 1,
+            retryable_status_codes: vec![503],
+            ..RetryPolicy::default()
+        })
+        .build()
+        .unwrap();
+    let mut saver = MemorySaver::default();
+
+    Scraper::new(discord_api)
+        .scrape_channel_with_saver(55, &mut saver)
+        .await
+        .unwrap();
+
+    assert_eq!(saved_message_ids(&saver), vec![1]);
+}
+
+#[tokio::test]
+async fn scrape_channel_gives_up_after_the_last_rate_limited_attempt() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 56, 1);
+    transport.respond_json(
+        "channels/56/messages?limit=100",
+        StatusCode::TOO_MANY_REQUESTS,
+        &json!({ "message": "You are being rate limited.", "retry_after": 0.0 }),
+    );
+    let discord_api = DiscordApi::builder("token", false)
+        .transport(Arc::clone(&transport))
+        .retry_policy(RetryPolicy {
+            max_rate_limited_attempts: 4,
+            ..RetryPolicy::default()
+        })
+        .build()
+        .unwrap();
+
+    let error = Scraper::new(discord_api)
+        .scrape_channel_with_saver(56, &mut MemorySaver::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        error,
+        ScraperError::DiscordApiError(DiscordApiError::RetriesExhausted(4, _))
+    ));
+    let message_requests = transport
+        .requested_urls()
+        .iter()
+        .filter(|url| *url == "channels/56/messages?limit=100")
+        .count();
+    assert_eq!(message_requests, 4);
+}
+
+#[tokio::test]
+async fn scrape_channel_quarantines_unparseable_messages() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 60, 2);
+    transport.respond_json(
+        "channels/60/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(2, "fine"), { "id": "1", "content": "no author" }]),
+    );
+    transport.respond_json(
+        "channels/60/messages?before=1&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let mut saver = MemorySaver::default();
+
+    let summary = scraper(&transport)
+        .scrape_channel_with_saver(60, &mut saver)
+        .await
+        .unwrap();
+
+    assert_eq!(saved_message_ids(&saver), vec![2]);
+    assert_eq!(summary.rejected_messages, 1);
+    assert_eq!(saver.rejected_messages[0].message_id, Some(1));
+}
+
+#[tokio::test]
+async fn scrape_channel_fails_on_unparseable_messages_in_strict_mode() {
+    let transport = Arc::new(FakeTransport::default());
+    text_channel(&transport, 70, 2);
+    transport.respond_json(
+        "channels/70/messages?limit=100",
+        StatusCode::OK,
+        &json!([message(2, "fine"), { "id": "1", "content": "no author" }]),
+    );
+
+    let error = scraper(&transport)
+        .with_parse_mode(ParseMode::Strict)
+        .scrape_channel_with_saver(70, &mut MemorySaver::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, ScraperError::RejectedMessages(1, 70, _)));
+}
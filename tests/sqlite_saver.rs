@@ -0,0 +1,367 @@
+use discord_rust_scraper::discord_api::FakeTransport;
+use discord_rust_scraper::discord_api::RejectedMessage;
+use discord_rust_scraper::utils::checkpoint::ScrapeCheckpoint;
+use discord_rust_scraper::utils::sql_schema::{self, SQLITE_COLUMNS_QUERY, SQLITE_MIGRATIONS};
+use discord_rust_scraper::utils::sqlite_saver::SqliteSaver;
+use discord_rust_scraper::{DiscordApi, Message, MessageSaver, SaveTarget, Scraper};
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use std::path::PathBuf;
+
+fn database_path(name: &str) -> PathBuf {
+    let database_path = std::env::temp_dir().join(format!(
+        "discord_rust_scraper_{}_{}.db",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&database_path);
+    database_path
+}
+
+#[tokio::test]
+async fn scrape_channel_into_a_new_sqlite_database() {
+    let transport = FakeTransport::default();
+    transport.respond_json(
+        "channels/1",
+        StatusCode::OK,
+        &json!({ "id": "1", "type": 0, "name": "general", "last_message_id": "1235526416413966397" }),
+    );
+    transport.respond_json(
+        "channels/1/messages?limit=100",
+        StatusCode::OK,
+        &json!([
+            {
+                "id": "1235526416413966397",
+                "author": { "id": "80351110224678912", "username": "ferris" },
+                "content": "hello from sqlite",
+                "timestamp": "2024-05-02T10:15:30.125000+00:00",
+                "attachments": [{ "id": "5", "filename": "a.png", "size": 1, "url": "https://cdn" }],
+            }
+        ]),
+    );
+    transport.respond_json(
+        "channels/1/messages?before=1235526416413966397&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let database_path = database_path("scrape");
+    let scraper = Scraper::new(
+        DiscordApi::builder("token", false)
+            .transport(transport)
+            .build()
+            .unwrap(),
+    );
+
+    let summary = scraper
+        .scrape_channel(1, &SaveTarget::Sqlite(database_path.clone()))
+        .await
+        .unwrap();
+
+    assert_eq!(summary.saved_messages, 1);
+    assert_eq!(summary.output_path, Some(database_path.clone()));
+    let pool = SqlitePool::connect(database_path.to_str().unwrap())
+        .await
+        .unwrap();
+    let (message_id, message, has_media, author_name): (i64, String, bool, String) =
+        sqlx::query_as("SELECT message_id, message, has_media, author_name FROM messages")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(message_id, 1235526416413966397);
+    assert_eq!(message, "hello from sqlite");
+    assert!(has_media);
+    assert_eq!(author_name, "ferris");
+    pool.close().await;
+    std::fs::remove_file(&database_path).unwrap();
+}
+
+#[tokio::test]
+async fn scrape_channel_records_its_channel_guild_and_scrape_run() {
+    let transport = FakeTransport::default();
+    transport.respond_json(
+        "channels/1",
+        StatusCode::OK,
+        &json!({ "id": "1", "type": 0, "name": "general", "guild_id": "7", "parent_id": "2" }),
+    );
+    transport.respond_json(
+        "channels/1/messages?limit=100",
+        StatusCode::OK,
+        &json!([
+            {
+                "id": "10",
+                "author": { "id": "3", "username": "ferris" },
+                "content": "hello",
+                "timestamp": "2024-05-02T10:15:30+00:00",
+            },
+            { "id": "9", "content": "no author" }
+        ]),
+    );
+    transport.respond_json(
+        "channels/1/messages?before=9&limit=100",
+        StatusCode::OK,
+        &json!([]),
+    );
+    let database_path = database_path("scrape_runs");
+    let scraper = Scraper::new(
+        DiscordApi::builder("token", false)
+            .transport(transport)
+            .build()
+            .unwrap(),
+    );
+
+    scraper
+        .scrape_channel(1, &SaveTarget::Sqlite(database_path.clone()))
+        .await
+        .unwrap();
+
+    let pool = SqlitePool::connect(database_path.to_str().unwrap())
+        .await
+        .unwrap();
+    let channels: Vec<(i64, i64, i64, String, i64)> = sqlx::query_as(
+        "SELECT channel_id, guild_id, parent_channel_id, name, channel_type FROM channels",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(channels, vec![(1, 7, 2, "general".to_string(), 0)]);
+    let guilds: Vec<i64> = sqlx::query_scalar("SELECT guild_id FROM guilds")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(guilds, vec![7]);
+    let scrape_runs: Vec<(i64, i64, i64, bool, bool)> = sqlx::query_as(
+        "SELECT channel_id, saved_messages, rejected_messages, completed, finished_at IS NOT NULL FROM scrape_runs",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(scrape_runs, vec![(1, 1, 1, true, true)]);
+    pool.close().await;
+    std::fs::remove_file(&database_path).unwrap();
+}
+
+#[tokio::test]
+async fn sqlite_saver_keeps_checkpoints() {
+    let database_path = database_path("checkpoints");
+    let mut saver = SqliteSaver::new(&database_path).await.unwrap();
+    let mut checkpoint = ScrapeCheckpoint::new(1);
+    checkpoint.oldest_message_id = Some(10);
+    checkpoint.newest_message_id = Some(u64::MAX >> 1);
+    saver.save_checkpoint(&checkpoint).await.unwrap();
+    checkpoint.completed = true;
+    saver.save_checkpoint(&checkpoint).await.unwrap();
+
+    let loaded_checkpoint = saver.load_checkpoint(1).await.unwrap().unwrap();
+
+    assert_eq!(loaded_checkpoint.oldest_message_id, Some(10));
+    assert_eq!(loaded_checkpoint.newest_message_id, Some(u64::MAX >> 1));
+    assert!(loaded_checkpoint.completed);
+    assert!(saver.load_checkpoint(2).await.unwrap().is_none());
+    assert_eq!(saver.newest_archived_message_id(1).await.unwrap(), None);
+    std::fs::remove_file(&database_path).unwrap();
+}
+
+#[tokio::test]
+async fn migrations_are_applied_once_and_adopt_existing_tables() {
+    let database_path = database_path("migrations");
+    let pool = SqlitePool::connect_with(
+        SqliteConnectOptions::new()
+            .filename(&database_path)
+            .create_if_missing(true),
+    )
+    .await
+    .unwrap();
+    sqlx::query(
+        "CREATE TABLE scrape_checkpoints (channel_id INTEGER NOT NULL PRIMARY KEY, oldest_message_id INTEGER NULL, newest_message_id INTEGER NULL, completed BOOLEAN NOT NULL)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO scrape_checkpoints VALUES (1, NULL, NULL, TRUE)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let applied_versions = sql_schema::migrate(&pool, SQLITE_MIGRATIONS, SQLITE_COLUMNS_QUERY)
+        .await
+        .unwrap();
+    let reapplied_versions = sql_schema::migrate(&pool, SQLITE_MIGRATIONS, SQLITE_COLUMNS_QUERY)
+        .await
+        .unwrap();
+
+    assert_eq!(applied_versions, vec![1, 2, 3, 4, 5]);
+    assert!(reapplied_versions.is_empty());
+    let tables: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    for table in [
+        "attachments",
+        "authors",
+        "channels",
+        "guilds",
+        "messages",
+        "scrape_checkpoints",
+        "scrape_runs",
+        "schema_migrations",
+    ] {
+        assert!(
+            tables.iter().any(|name| name == table),
+            "missing `{}`",
+            table
+        );
+    }
+    let checkpoints: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM scrape_checkpoints")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(checkpoints, 1);
+    pool.close().await;
+    std::fs::remove_file(&database_path).unwrap();
+}
+
+#[tokio::test]
+async fn migrations_add_the_missing_columns_of_a_baseline_messages_table() {
+    let database_path = database_path("baseline");
+    let pool = SqlitePool::connect_with(
+        SqliteConnectOptions::new()
+            .filename(&database_path)
+            .create_if_missing(true),
+    )
+    .await
+    .unwrap();
+    sqlx::query(
+        "CREATE TABLE messages (channel_id INTEGER NOT NULL, author_id INTEGER NOT NULL, message_id INTEGER NOT NULL PRIMARY KEY, message TEXT NOT NULL, has_media BOOLEAN NOT NULL)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO messages VALUES (1, 3, 10, 'from the baseline', FALSE)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    pool.close().await;
+
+    let mut saver = SqliteSaver::new(&database_path).await.unwrap();
+    let page: Vec<Message> = serde_json::from_value(json!([{
+        "channel_id": 1,
+        "author_id": 3,
+        "message_id": 20,
+        "message": "after the migration",
+        "has_media": false,
+        "author": { "id": "3", "username": "ferris" },
+    }]))
+    .unwrap();
+    saver.save_messages(&page).await.unwrap();
+    let messages = saver.channel_messages(1).await.unwrap();
+
+    std::fs::remove_file(&database_path).unwrap();
+    let contents: Vec<&str> = messages
+        .iter()
+        .map(|message| message.message.as_str())
+        .collect();
+    assert_eq!(contents, vec!["from the baseline", "after the migration"]);
+    assert!(messages[0].author.is_none());
+    assert_eq!(messages[1].author.as_ref().unwrap().username, "ferris");
+}
+
+#[tokio::test]
+async fn saving_a_page_again_updates_it_in_place() {
+    let database_path = database_path("upserts");
+    let mut saver = SqliteSaver::new(&database_path).await.unwrap();
+    let page = |content: &str| -> Vec<Message> {
+        serde_json::from_value(json!([
+            {
+                "channel_id": 1,
+                "author_id": 3,
+                "message_id": 20,
+                "message": content,
+                "has_media": true,
+                "author": { "id": "3", "username": "ferris" },
+                "attachments": [{ "id": "5", "filename": "a.png", "size": 1, "url": "https://cdn" }],
+            },
+            {
+                "channel_id": 1,
+                "author_id": 3,
+                "message_id": 10,
+                "message": "unchanged",
+                "has_media": false,
+                "author": { "id": "3", "username": "ferris" },
+            }
+        ]))
+        .unwrap()
+    };
+    saver.save_messages(&page("first draft")).await.unwrap();
+
+    saver.save_messages(&page("edited")).await.unwrap();
+
+    let pool = SqlitePool::connect(database_path.to_str().unwrap())
+        .await
+        .unwrap();
+    let messages: Vec<(i64, String)> =
+        sqlx::query_as("SELECT message_id, message FROM messages ORDER BY message_id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        messages,
+        vec![(10, "unchanged".to_string()), (20, "edited".to_string())]
+    );
+    let authors: Vec<(i64, String)> = sqlx::query_as("SELECT author_id, username FROM authors")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(authors, vec![(3, "ferris".to_string())]);
+    let attachments: Vec<(i64, i64)> =
+        sqlx::query_as("SELECT attachment_id, message_id FROM attachments")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(attachments, vec![(5, 20)]);
+    pool.close().await;
+    std::fs::remove_file(&database_path).unwrap();
+}
+
+#[tokio::test]
+async fn saving_a_rejected_message_again_updates_it_in_place() {
+    let database_path = database_path("rejected");
+    let mut saver = SqliteSaver::new(&database_path).await.unwrap();
+    let rejected_message = |message_id: Option<u64>, reason: &str| RejectedMessage {
+        channel_id: 1,
+        message_id,
+        reason: reason.to_string(),
+        raw: json!({ "id": message_id.map(|message_id| message_id.to_string()) }),
+    };
+
+    saver
+        .save_rejected_messages(&[
+            rejected_message(Some(20), "missing author"),
+            rejected_message(None, "no id"),
+        ])
+        .await
+        .unwrap();
+    saver
+        .save_rejected_messages(&[rejected_message(Some(20), "missing content")])
+        .await
+        .unwrap();
+
+    let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(&database_path))
+        .await
+        .unwrap();
+    let rows: Vec<(Option<i64>, String)> =
+        sqlx::query_as("SELECT message_id, reason FROM rejected_messages ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    pool.close().await;
+    std::fs::remove_file(&database_path).unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            (Some(20), "missing content".to_string()),
+            (None, "no id".to_string())
+        ]
+    );
+}
@@ -0,0 +1,250 @@
+use common::{temp_files, TempFiles};
+use discord_rust_scraper::scraper::{
+    convert_jsonl_file_into_html, convert_jsonl_file_into_transcript,
+};
+use discord_rust_scraper::transcript::html::DEFAULT_MESSAGES_PER_PAGE;
+use discord_rust_scraper::transcript::TranscriptFormat;
+use discord_rust_scraper::utils::sqlite_saver::SqliteSaver;
+use discord_rust_scraper::{Message, MessageSaver, ParseMode};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+mod common;
+
+fn messages() -> Vec<Value> {
+    vec![
+        json!({
+            "channel_id": 1,
+            "author_id": 3,
+            "message_id": 1235526100012310578_u64,
+            "message": "**hello** <@4>, see <#1> and <@&9> at https://example.com/a_b_c",
+            "has_media": false,
+            "timestamp": "2024-05-02T09:39:37.000000+00:00",
+            "author": { "id": "3", "username": "ferris", "global_name": "Ferris" },
+            "mentions": [{ "id": "4", "username": "corro" }],
+        }),
+        json!({
+            "channel_id": 1,
+            "author_id": 3,
+            "message_id": 1235526416413966397_u64,
+            "message": "`<b>` stays code <script>",
+            "has_media": true,
+            "timestamp": "2024-05-02T09:40:52.000000+00:00",
+            "edited_timestamp": "2024-05-02T09:41:00.000000+00:00",
+            "author": { "id": "3", "username": "ferris", "global_name": "Ferris" },
+            "attachments": [
+                { "id": "5", "filename": "crab.png", "size": 1500000, "url": "https://cdn/crab.png" }
+            ],
+        }),
+        json!({
+            "channel_id": 1,
+            "author_id": 4,
+            "message_id": 1235530000000000000_u64,
+            "message": "",
+            "has_media": false,
+            "timestamp": "2024-05-02T09:55:06.000000+00:00",
+            "type": 19,
+            "author": { "id": "4", "username": "corro" },
+            "message_reference": { "message_id": "1235526100012310578" },
+            "embeds": [{ "title": "Rust", "url": "https://rust-lang.org", "description": "_fast_" }],
+        }),
+    ]
+}
+
+fn write_jsonl_archive(temp_files: &TempFiles, name: &str) -> PathBuf {
+    let jsonl_path = temp_files.path(name);
+    let lines: Vec<String> = messages().iter().map(Value::to_string).collect();
+    std::fs::write(&jsonl_path, lines.join("\n")).unwrap();
+    jsonl_path
+}
+
+#[tokio::test]
+async fn render_jsonl_archive_into_an_html_transcript() {
+    let temp_files = temp_files();
+    let jsonl_path = write_jsonl_archive(&temp_files, "general.jsonl");
+
+    let page_paths = convert_jsonl_file_into_html(&jsonl_path, ParseMode::Strict, 1000)
+        .await
+        .unwrap();
+
+    assert_eq!(page_paths, vec![jsonl_path.with_extension("html")]);
+    let html = std::fs::read_to_string(&page_paths[0]).unwrap();
+    assert!(html.contains("<title>general</title>"));
+    // The first two messages share the header of their author.
+    assert_eq!(html.matches("<article class=\"group\">").count(), 2);
+    assert_eq!(html.matches("<span class=\"author\"").count(), 2);
+    assert_eq!(html.matches("<div class=\"day\">").count(), 1);
+    assert!(html.contains("<div class=\"day\"><span>Thursday, 2 May 2024</span></div>"));
+    assert!(html.contains("<time datetime=\"2024-05-02T09:39:37+00:00\">2024-05-02 09:39</time>"));
+    assert!(html.contains(
+        "<strong>hello</strong> <span class=\"mention\">@corro</span>, see <span class=\"mention\">#general</span>"
+    ));
+    assert!(html.contains("<span class=\"mention\">@9</span>"));
+    assert!(html.contains(
+        "<a href=\"https://example.com/a_b_c\" rel=\"noreferrer\">https://example.com/a_b_c</a>"
+    ));
+    assert!(html.contains("<code>&lt;b&gt;</code> stays code &lt;script&gt;"));
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("(edited)</span>"));
+    assert!(html.contains(
+        "<a href=\"https://cdn/crab.png\" rel=\"noreferrer\">crab.png</a><span class=\"size\">1.5 MB</span>"
+    ));
+    assert!(html.contains(
+        "<span class=\"reply-author\">@Ferris</span> <a href=\"general.html#message-1235526100012310578\">"
+    ));
+    assert!(html.contains(
+        "<div class=\"embed-title\"><a href=\"https://rust-lang.org\" rel=\"noreferrer\">Rust</a></div><div class=\"embed-description\"><em>fast</em></div>"
+    ));
+}
+
+#[tokio::test]
+async fn render_paginates_the_transcript() {
+    let temp_files = temp_files();
+    let jsonl_path = write_jsonl_archive(&temp_files, "paginated.jsonl");
+
+    let page_paths = convert_jsonl_file_into_html(&jsonl_path, ParseMode::Strict, 2)
+        .await
+        .unwrap();
+
+    let second_page_path = temp_files.path("paginated.page-2.html");
+    assert_eq!(
+        page_paths,
+        vec![jsonl_path.with_extension("html"), second_page_path.clone()]
+    );
+    let first_page = std::fs::read_to_string(&page_paths[0]).unwrap();
+    let second_page = std::fs::read_to_string(&page_paths[1]).unwrap();
+    let prefix = "paginated";
+    assert!(first_page.contains(&format!(
+        "<span>Page 1 of 2</span><a href=\"{}.page-2.html\">Next &rarr;</a>",
+        prefix
+    )));
+    assert!(second_page.contains(&format!(
+        "<a href=\"{}.html\">&larr; Previous</a><span>Page 2 of 2</span>",
+        prefix
+    )));
+    assert!(first_page.contains("id=\"message-1235526416413966397\""));
+    assert!(!second_page.contains("id=\"message-1235526416413966397\""));
+    // Replies link to the page of the message they answer.
+    assert!(second_page.contains(&format!(
+        "<a href=\"{}.html#message-1235526100012310578\">",
+        prefix
+    )));
+}
+
+#[tokio::test]
+async fn read_back_the_messages_of_a_sqlite_database() {
+    let temp_files = temp_files();
+    let database_path = temp_files.path("transcript.db");
+    let mut messages: Vec<Message> = messages()
+        .into_iter()
+        .map(|message| serde_json::from_value(message).unwrap())
+        .collect();
+    messages[2].channel_id = 2;
+    messages[2].parent_channel_id = Some(1);
+    let other_channel_message = Message {
+        channel_id: 7,
+        message_id: 1235530000000000001,
+        ..messages[0].clone()
+    };
+    let mut saver = SqliteSaver::new(&database_path).await.unwrap();
+    saver.save_messages(&messages).await.unwrap();
+    saver.save_messages(&[other_channel_message]).await.unwrap();
+
+    let read_messages = saver.channel_messages(1).await.unwrap();
+
+    let message_ids: Vec<u64> = read_messages
+        .iter()
+        .map(|message| message.message_id)
+        .collect();
+    assert_eq!(
+        message_ids,
+        vec![
+            1235526100012310578,
+            1235526416413966397,
+            1235530000000000000
+        ]
+    );
+    let author = read_messages[0].author.as_ref().unwrap();
+    assert_eq!(author.username, "ferris");
+    assert_eq!(author.global_name.as_deref(), Some("Ferris"));
+    assert_eq!(read_messages[1].attachments[0].filename, "crab.png");
+    assert!(read_messages[1].edited_timestamp.is_some());
+    assert_eq!(read_messages[2].parent_channel_id, Some(1));
+    assert_eq!(
+        read_messages[2].reply_to_message_id(),
+        Some(1235526100012310578)
+    );
+    assert_eq!(read_messages[2].embeds[0].title.as_deref(), Some("Rust"));
+}
+
+#[tokio::test]
+async fn render_jsonl_archive_into_a_markdown_transcript() {
+    let temp_files = temp_files();
+    let jsonl_path = write_jsonl_archive(&temp_files, "markdown_channel.jsonl");
+
+    let file_paths = convert_jsonl_file_into_transcript(
+        &jsonl_path,
+        ParseMode::Strict,
+        TranscriptFormat::Markdown,
+        DEFAULT_MESSAGES_PER_PAGE,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(file_paths, vec![jsonl_path.with_extension("md")]);
+    let markdown = std::fs::read_to_string(&file_paths[0]).unwrap();
+    // Markdown escapes the underscores of the title, not those of channel mentions.
+    let title = "markdown\\_channel";
+    let channel_name = "markdown_channel";
+    assert_eq!(
+        markdown,
+        format!(
+            "# {}\n\n\
+             3 messages, times are in UTC.\n\n\
+             ## Thursday, 2 May 2024\n\n\
+             **Ferris** · 09:39\n\n\
+             **hello** @corro, see #{} and @9 at https://example.com/a_b_c\n\n\
+             `<b>` stays code <script> *(edited)*\n\n\
+             - Attachment: [crab.png](https://cdn/crab.png) (1.5 MB)\n\n\
+             **corro** · 09:55 · replying to **Ferris**\n\n\
+             - Embed: [Rust](https://rust-lang.org)\n",
+            title, channel_name
+        )
+    );
+}
+
+#[tokio::test]
+async fn render_jsonl_archive_into_a_plain_text_transcript() {
+    let temp_files = temp_files();
+    let jsonl_path = write_jsonl_archive(&temp_files, "text.jsonl");
+
+    let file_paths = convert_jsonl_file_into_transcript(
+        &jsonl_path,
+        ParseMode::Strict,
+        TranscriptFormat::Text,
+        DEFAULT_MESSAGES_PER_PAGE,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(file_paths, vec![jsonl_path.with_extension("txt")]);
+    let text = std::fs::read_to_string(&file_paths[0]).unwrap();
+    let channel_name = "text";
+    assert_eq!(
+        text,
+        format!(
+            "{}\n{}\n\n\
+             3 messages, times are in UTC.\n\n\
+             --- Thursday, 2 May 2024 ---\n\n\
+             [09:39] Ferris:\n  \
+             **hello** @corro, see #{} and @9 at https://example.com/a_b_c\n  \
+             `<b>` stays code <script> (edited)\n  \
+             [attachment: crab.png (1.5 MB) https://cdn/crab.png]\n\n\
+             [09:55] corro, replying to Ferris:\n  \
+             [embed: Rust https://rust-lang.org]\n",
+            channel_name,
+            "=".repeat(channel_name.len()),
+            channel_name
+        )
+    );
+}